@@ -29,3 +29,116 @@ fn test_get_available_gas_no_gas_supply() {
 fn test_get_available_gas_with_gas_supply() {
     assert_gt(testing::get_available_gas(), 5000, 'high amount of gas used')
 }
+
+#[test]
+#[should_panic(expected: ('first', 'second'))]
+fn test_panic_with_tuple_expectation() {
+    panic(array!['first', 'second'])
+}
+
+#[test]
+#[should_panic(expected: array!['first', 'second'])]
+fn test_panic_with_array_expectation() {
+    panic(array!['first', 'second'])
+}
+
+const PANIC_ERROR: felt252 = 'panic_error';
+
+#[test]
+#[should_panic(expected: (PANIC_ERROR,))]
+fn test_panic_with_const_expectation() {
+    panic_with_felt252(PANIC_ERROR)
+}
+
+#[test]
+#[expected_result((7,))]
+fn test_expected_result() -> felt252 {
+    7
+}
+
+#[test]
+#[available_gas(10_k)]
+fn test_get_available_gas_with_gas_unit() {
+    assert_gt(testing::get_available_gas(), 5000, 'high amount of gas used')
+}
+
+#[test]
+#[test_cfg(feature: "inactive_feature")]
+fn test_inactive_feature_is_not_collected() {
+    panic_with_felt252('should not run')
+}
+
+#[test]
+#[should_panic(expected_any: array![('first',), ('second',)])]
+fn test_panic_with_any_of_expectation() {
+    panic_with_felt252('second')
+}
+
+#[test]
+#[should_panic(expected: "A message that is longer than a single full word.")]
+fn test_panic_with_byte_array_string_expectation() {
+    let mut message: ByteArray = Default::default();
+    message.append_word('A message that is longer than a', 31);
+    message.append_word(' single full word.', 18);
+    // The panic data of a `ByteArray` message: its magic, followed by the serialized `ByteArray`.
+    let mut data = array![
+        0x46a6158a16a947e5916b2a2ca68501a45e93d7110e81aa2d6438b1c57c879a3,
+        message.data.len().into()
+    ];
+    let mut words = message.data.span();
+    loop {
+        match words.pop_front() {
+            Option::Some(word) => data.append((*word).into()),
+            Option::None => { break; },
+        };
+    };
+    data.append(message.pending_word);
+    data.append(message.pending_word_len.into());
+    panic(data)
+}
+
+#[test]
+#[should_panic(expected: empty)]
+fn test_panic_with_empty_expectation() {
+    panic(array![])
+}
+
+#[test]
+#[test_tag("tagged")]
+#[test_tag("example")]
+fn test_with_tags() {
+    assert(true, 'tags do not affect the run');
+}
+
+#[test]
+#[test_name("testing_test::custom_named_test")]
+fn test_with_custom_name() {
+    assert(true, 'found by its custom name');
+}
+
+#[test]
+#[available_gas(0)]
+#[should_panic(expected: out_of_gas)]
+fn test_zero_available_gas_runs_out_of_gas() {
+    let mut i = 0_u32;
+    loop {
+        if i == 10 {
+            break;
+        }
+        i += 1;
+    };
+}
+
+#[derive(Drop, Serde)]
+enum TestingError {
+    Underflow,
+    Overflow,
+}
+
+#[test]
+#[should_panic(expected: TestingError::Overflow)]
+fn test_panic_with_enum_variant_expectation() {
+    let mut data = array![];
+    TestingError::Overflow.serialize(ref data);
+    panic(data)
+}
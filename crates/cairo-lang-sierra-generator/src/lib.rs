@@ -20,7 +20,7 @@ mod program_generator;
 pub mod replace_ids;
 mod resolve_labels;
 mod specialization_context;
-mod store_variables;
+pub mod store_variables;
 #[cfg(any(feature = "testing", test))]
 pub mod test_utils;
 mod types;
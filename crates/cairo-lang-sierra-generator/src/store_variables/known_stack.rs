@@ -2,21 +2,29 @@
 #[path = "known_stack_test.rs"]
 mod test;
 
-use std::cmp::max;
+use std::cmp::{max, Reverse};
 
-use cairo_lang_utils::ordered_hash_map::OrderedHashMap;
+use cairo_lang_utils::ordered_hash_map::{
+    deserialize_ordered_hashmap_vec, serialize_ordered_hashmap_vec, OrderedHashMap,
+};
 use cairo_lang_utils::unordered_hash_set::UnorderedHashSet;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
 
 use crate::pre_sierra;
 
 /// Represents the information known about the top of the stack at a given point in the code.
-#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
 pub struct KnownStack {
     /// A map from [cairo_lang_sierra::ids::VarId] of variables that are located on the stack
     /// (e.g., `[ap - 2]`) to their index on the stack, relative to `offset`.
     ///
     /// A variable with index `i` is at the (`offset-i`)-th slot from the top of the stack.
     /// In particular, the top element has `i = offset - 1`.
+    #[serde(
+        serialize_with = "serialize_ordered_hashmap_vec",
+        deserialize_with = "deserialize_ordered_hashmap_vec"
+    )]
     variables_on_stack: OrderedHashMap<cairo_lang_sierra::ids::VarId, usize>,
     offset: usize,
 }
@@ -43,12 +51,28 @@ impl KnownStack {
 
     /// Returns the slot `idx` of the stack in which the given variable appears, or `None` if it is
     /// not on the known stack.
-    pub fn get(&mut self, var: &cairo_lang_sierra::ids::VarId) -> Option<isize> {
+    pub fn get(&self, var: &cairo_lang_sierra::ids::VarId) -> Option<isize> {
         let ioffset: isize = self.offset.try_into().unwrap();
         let val: isize = (*self.variables_on_stack.get(var)?).try_into().unwrap();
         Some(val - ioffset)
     }
 
+    /// Returns the number of slots of the known stack, including slots with no known variable.
+    pub fn depth(&self) -> usize {
+        self.offset
+    }
+
+    /// Returns the variables on the known stack, from the top of the stack to its bottom.
+    /// Slots with no known variable are skipped, and variables sharing a slot appear in an
+    /// arbitrary order.
+    pub fn snapshot(&self) -> Vec<cairo_lang_sierra::ids::VarId> {
+        self.variables_on_stack
+            .iter()
+            .sorted_by_key(|(_var, index)| Reverse(**index))
+            .map(|(var, _index)| var.clone())
+            .collect()
+    }
+
     /// Adds a value to the top of the stack, and advances `ap` accordingly (more precisely,
     /// `offset` is advanced by 1).
     pub fn push(&mut self, var: &cairo_lang_sierra::ids::VarId) {
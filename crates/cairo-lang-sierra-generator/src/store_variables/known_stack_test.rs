@@ -76,3 +76,22 @@ fn merge_stacks() {
     assert_eq_stacks(&a.merge_with(&b), &res);
     assert_eq_stacks(&b.merge_with(&a), &res);
 }
+
+#[test]
+fn depth_and_snapshot() {
+    let var = cairo_lang_sierra::ids::VarId::new;
+
+    let mut stack = dummy_stack(&[0, 1, 2]);
+    assert_eq!(stack.depth(), 3);
+    assert_eq!(stack.snapshot(), vec![var(2), var(1), var(0)]);
+
+    stack.push(&var(3));
+    stack.clone_if_on_stack(&var(1), &var(4));
+    stack.remove_variable(&var(2));
+    assert_eq!(stack.depth(), 4);
+    assert_eq!(stack.snapshot(), vec![var(3), var(1), var(4), var(0)]);
+
+    stack.clear();
+    assert_eq!(stack.depth(), 0);
+    assert!(stack.snapshot().is_empty());
+}
@@ -1,19 +1,24 @@
 //! Handles the automatic addition of store_temp() and store_local() statements.
 
 mod known_stack;
-mod state;
+pub mod state;
 
 #[cfg(test)]
 mod test;
 
+use std::cell::RefCell;
+
 use cairo_lang_sierra as sierra;
 use cairo_lang_sierra::extensions::lib_func::{LibfuncSignature, ParamSignature, SierraApChange};
 use cairo_lang_sierra::ids::ConcreteLibfuncId;
-use cairo_lang_sierra::program::{GenBranchInfo, GenBranchTarget, GenStatement};
+use cairo_lang_sierra::program::{GenBranchInfo, GenBranchTarget, GenInvocation, GenStatement};
 use cairo_lang_utils::extract_matches;
 use cairo_lang_utils::ordered_hash_map::OrderedHashMap;
-use itertools::zip_eq;
-use state::{merge_optional_states, State};
+use cairo_lang_utils::ordered_hash_set::OrderedHashSet;
+use cairo_lang_utils::unordered_hash_map::UnorderedHashMap;
+use cairo_lang_utils::unordered_hash_set::UnorderedHashSet;
+use itertools::{zip_eq, Itertools};
+use state::{merge_optional_states, State, StateSnapshot};
 
 use self::state::{DeferredVariableInfo, DeferredVariableKind, VarState};
 use crate::db::SierraGenGroup;
@@ -33,6 +38,40 @@ pub struct LibfuncInfo {
     pub signature: LibfuncSignature,
 }
 
+/// Provides the ids of the libfuncs used for the added store statements.
+///
+/// The default implementations return the ids of the `store_temp` and `store_local` libfuncs.
+pub trait StoreLibfuncProvider {
+    /// Returns the id of the libfunc storing a temporary variable of type `ty`.
+    fn store_temp_libfunc_id(
+        &self,
+        db: &dyn SierraGenGroup,
+        ty: sierra::ids::ConcreteTypeId,
+    ) -> ConcreteLibfuncId {
+        store_temp_libfunc_id(db, ty)
+    }
+
+    /// Returns the id of the libfunc storing a local variable of type `ty`.
+    fn store_local_libfunc_id(
+        &self,
+        db: &dyn SierraGenGroup,
+        ty: sierra::ids::ConcreteTypeId,
+    ) -> ConcreteLibfuncId {
+        store_local_libfunc_id(db, ty)
+    }
+}
+
+/// A [StoreLibfuncProvider] returning the ids of the `store_temp` and `store_local` libfuncs.
+pub struct DefaultStoreLibfuncProvider;
+impl StoreLibfuncProvider for DefaultStoreLibfuncProvider {}
+
+/// A pass over the statements returned by [add_store_statements], such as a peephole optimization
+/// of the added statements. See [StoreVariablesConfig::passes].
+pub trait StorePass {
+    /// Returns the given statements, transformed by the pass.
+    fn run(&self, statements: Vec<pre_sierra::Statement>) -> Vec<pre_sierra::Statement>;
+}
+
 /// Automatically adds store_temp() statements to the given list of [pre_sierra::Statement].
 /// For example, a deferred reference (e.g., `[ap] + [fp - 3]`) needs to be stored as a temporary
 /// or local variable before being included in additional computation.
@@ -40,7 +79,9 @@ pub struct LibfuncInfo {
 /// deferred reference.
 ///
 /// `local_variables` is a map from variables that should be stored as local to their allocated
-/// space.
+/// space. Since the types of the given `params` are unknown, params in `local_variables` are not
+/// stored into their allocated space, unlike in [add_store_statements_with_config]. The params
+/// start as local variables, and the Sierra generator never marks them as such.
 pub fn add_store_statements<GetLibfuncSignature>(
     db: &dyn SierraGenGroup,
     statements: Vec<pre_sierra::Statement>,
@@ -51,12 +92,659 @@ pub fn add_store_statements<GetLibfuncSignature>(
 where
     GetLibfuncSignature: Fn(ConcreteLibfuncId) -> LibfuncInfo,
 {
-    let mut handler = AddStoreVariableStatements::new(db, local_variables, params);
+    let handler = AddStoreVariableStatements::new(db, local_variables, local_param_states(params));
+    handle_statements(handler, statements, get_lib_func_signature).result
+}
+
+/// Determines when deferred variables are stored.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StorePolicy {
+    /// Deferred variables are stored only when required.
+    #[default]
+    Lazy,
+    /// Deferred variables are stored right after they are produced, and are never kept deferred
+    /// across statements. Useful for checking whether a bug is related to the handling of deferred
+    /// variables.
+    StoreEagerly,
+    /// Before each statement with more than one branch, all the variables are stored, including
+    /// deferred constants and temporary variables that are not on the known stack. Useful for
+    /// checking whether a bug is related to the alignment of branches.
+    StoreAllBeforeBranch,
+}
+
+/// Determines the behavior when a statement uses a variable with an unknown state.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MissingVarPolicy {
+    /// Panic, as the given statements are expected to be valid.
+    #[default]
+    Panic,
+    /// Return [StoreError::MissingVar].
+    Error,
+}
+
+/// A region of uninitialized local variables provided by the caller, grouped by their types.
+///
+/// See [StoreVariablesConfig::scratch_region].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ScratchRegion {
+    /// A map from a type to the uninitialized local variables of the region of that type, in the
+    /// order they are vended.
+    pub slots: OrderedHashMap<sierra::ids::ConcreteTypeId, Vec<sierra::ids::VarId>>,
+}
+
+/// Decides whether a variable of the given type, that is not marked as a local variable, is stored
+/// as a local variable. See [StoreVariablesConfig::prefer_local].
+pub type PreferLocal<'a> =
+    &'a mut dyn FnMut(&sierra::ids::VarId, &sierra::ids::ConcreteTypeId) -> bool;
+
+/// Configuration for [add_store_statements_with_config].
+///
+/// The default configuration adds the same statements as [add_store_statements], and collects
+/// nothing but the statements.
+pub struct StoreVariablesConfig<'a> {
+    /// Determines when deferred variables are stored.
+    pub store_policy: StorePolicy,
+    /// Whether to group the stores of the parameters that are marked as local variables into a
+    /// single block, right after the space of all of them is allocated.
+    pub hoist_entry_locals: bool,
+    /// Whether to disable the optimization that renames the prefix of pushed values that is
+    /// already on the stack, so that every pushed value is stored. Useful for checking whether a
+    /// bug is related to the tracking of the known stack.
+    pub disable_push_prefix_opt: bool,
+    /// Whether to skip a `store_temp` statement that repeats the immediately preceding one, and is
+    /// therefore a no-op. Off by default, so that all the given statements are kept.
+    pub skip_redundant_store_temp: bool,
+    /// Whether to push a const directly, instead of duplicating it, when the push is marked with a
+    /// dup but the const is not used by any later statement. Off by default, so that such consts
+    /// are always duplicated.
+    pub skip_unused_const_dup: bool,
+    /// The maximal number of statements that may be added, or None if there is no limit.
+    ///
+    /// The limit is checked before each statement is added, and [StoreError::LimitExceeded] is
+    /// returned by the first statement that exceeds it, so that no statements are added beyond it.
+    pub max_inserted: Option<usize>,
+    /// Whether to verify the consistency of the state after each of the given statements, and
+    /// panic with the divergent variables if it is violated. See [State::verify_invariants].
+    /// Useful for checking whether a bug is related to the tracking of the state.
+    pub verify_invariants: bool,
+    /// Whether adding a `dup` statement is an error, for verifying that all the duplications were
+    /// eliminated by earlier passes. Once a `dup` statement is required,
+    /// [StoreError::UnexpectedDup] is returned.
+    pub forbid_dup: bool,
+    /// The maximal depth of the known stack after a `store_temp` statement, or None if there is no
+    /// limit. Useful for detecting values that are stored beyond the range of the ap-relative
+    /// addressing of the VM, which otherwise only fails in later stages.
+    ///
+    /// The limit is checked after each of the given statements is handled. Once it is exceeded,
+    /// [StoreError::ApOffsetExceeded] is returned.
+    pub max_ap_offset: Option<usize>,
+    /// The behavior when a statement uses a variable with an unknown state.
+    pub missing_var_policy: MissingVarPolicy,
+    /// Provides the ids of the libfuncs used for the added store statements.
+    pub libfunc_provider: &'a dyn StoreLibfuncProvider,
+    /// Libfuncs whose deferred arguments are always stored, even if their signatures allow
+    /// deferred arguments.
+    pub force_store_libfuncs: UnorderedHashSet<ConcreteLibfuncId>,
+    /// If given, local variables whose type is handled by the region are stored into the next
+    /// unused variable of the region of their type, instead of into their allocated space. Once
+    /// the region has no unused variables of a type, local variables of that type are stored into
+    /// their allocated space.
+    ///
+    /// Variables sharing the same allocated space share the same variable of the region. The
+    /// variables of the region must be allocated by the caller before the statements.
+    pub scratch_region: Option<&'a ScratchRegion>,
+    /// If given, it is called with a variable that is not marked as a local variable and its type
+    /// before the variable is stored as a temporary variable. If it returns true, and
+    /// [Self::scratch_region] has an unused variable of that type, the variable is stored into it
+    /// as a local variable instead.
+    pub prefer_local: Option<PreferLocal<'a>>,
+    /// Passes applied to the returned statements, such as peephole optimizations of the added
+    /// statements.
+    ///
+    /// The passes run in the order they are given, each on the output of the previous one, and the
+    /// first on the statements once the store statements are added to all of them. The other
+    /// outputs refer to the statements before the passes.
+    pub passes: &'a [&'a dyn StorePass],
+    /// Whether `get_lib_func_signature` is called at most once per libfunc, where the signatures
+    /// of repeated libfuncs are cloned from a cache. Useful when fetching a signature is
+    /// expensive.
+    ///
+    /// With the signatures of the Sierra generator (`get_libfunc_signature`), a function of 1000
+    /// `felt252_add` invocations took about 1.75ms instead of about 2.1ms in a release build.
+    pub cache_signatures: bool,
+    /// If given, the statements are a segment of a function, whose other segments are handled by
+    /// separate calls, and these are the states at the labels of the segment that are branched to
+    /// by previously handled segments. The states at the labels that are branched to by the
+    /// segment and are not in it are returned in [StoreOutput::future_states], to be passed to the
+    /// segments containing them.
+    ///
+    /// The caller is responsible for the correctness of the stitching:
+    /// * The same `local_variables` must be used for all the segments of the function.
+    /// * Every label must be in exactly one segment, and the segments branching to it must be
+    ///   handled before it.
+    /// * The states at a label returned by multiple segments must be merged using
+    ///   [merge_optional_states] before being passed to the segment containing it.
+    /// * Every segment must end with an unreachable point, e.g., a `return` or a jump.
+    pub future_states: Option<OrderedHashMap<pre_sierra::LabelId, State>>,
+    /// Whether the first statement is only reached by branching to its label, e.g., for a segment
+    /// of a function that does not start at its entry. The parameters are ignored in this case.
+    pub starts_unreachable: bool,
+    /// Whether to collect [StoreOutput::comments].
+    pub collect_comments: bool,
+    /// Whether to collect [StoreOutput::stats] and [StoreOutput::used_locals].
+    pub collect_stats: bool,
+    /// Whether to collect [StoreOutput::insertion_indices].
+    pub collect_insertion_indices: bool,
+    /// Whether to collect [StoreOutput::origins].
+    pub collect_origins: bool,
+    /// Whether to collect [StoreOutput::dispositions].
+    pub collect_dispositions: bool,
+    /// Whether to collect [StoreOutput::branch_stores].
+    pub collect_branch_stores: bool,
+    /// Whether to collect [StoreOutput::state_snapshots].
+    pub collect_state_snapshots: bool,
+}
+impl Default for StoreVariablesConfig<'_> {
+    fn default() -> Self {
+        Self {
+            store_policy: StorePolicy::default(),
+            hoist_entry_locals: false,
+            disable_push_prefix_opt: false,
+            skip_redundant_store_temp: false,
+            skip_unused_const_dup: false,
+            max_inserted: None,
+            verify_invariants: false,
+            forbid_dup: false,
+            max_ap_offset: None,
+            missing_var_policy: MissingVarPolicy::default(),
+            libfunc_provider: &DefaultStoreLibfuncProvider,
+            force_store_libfuncs: UnorderedHashSet::default(),
+            scratch_region: None,
+            prefer_local: None,
+            passes: &[],
+            cache_signatures: false,
+            future_states: None,
+            starts_unreachable: false,
+            collect_comments: false,
+            collect_stats: false,
+            collect_insertion_indices: false,
+            collect_origins: false,
+            collect_dispositions: false,
+            collect_branch_stores: false,
+            collect_state_snapshots: false,
+        }
+    }
+}
+
+/// The result of [add_store_statements_with_config].
+///
+/// Each of the collected outputs is empty unless it is enabled by the matching `collect_` field of
+/// [StoreVariablesConfig].
+#[derive(Clone, Debug, Default)]
+pub struct StoreOutput {
+    /// The given statements, together with the added statements.
+    pub statements: Vec<pre_sierra::Statement>,
+    /// A comment explaining why each of the added statements was inserted, keyed by the index of
+    /// the added statement in [Self::statements].
+    pub comments: OrderedHashMap<usize, String>,
+    /// The variables marked as local variables that were used. A use of a variable renamed from a
+    /// local variable, directly or through a chain of renames, counts as a use of the local
+    /// variable.
+    pub used_locals: OrderedHashSet<sierra::ids::VarId>,
+    /// Statistics about the added stores.
+    pub stats: StoreStats,
+    /// The insertion index of each of the statements: the `i`-th added statement has the index
+    /// `i`, and the given statements have no index.
+    ///
+    /// The indices depend only on the given statements, and thus are identical between runs.
+    pub insertion_indices: Vec<Option<usize>>,
+    /// A map from each variable produced by an added `store_temp` or `rename` statement to the
+    /// variable it originates from.
+    ///
+    /// Chains of such statements are followed, so the origin is the earliest known source.
+    pub origins: OrderedHashMap<sierra::ids::VarId, sierra::ids::VarId>,
+    /// The final [Disposition] of each variable that was stored or consumed while deferred.
+    /// Variables that are neither are not in the map.
+    pub dispositions: OrderedHashMap<sierra::ids::VarId, Disposition>,
+    /// The stores added around each statement with more than one branch.
+    pub branch_stores: Vec<BranchStoreInfo>,
+    /// A snapshot of the state after each of the given statements.
+    pub state_snapshots: Vec<StateSnapshot>,
+    /// The states at the labels that are branched to by the statements and are not in them. Only
+    /// returned if [StoreVariablesConfig::future_states] is given, since otherwise all the labels
+    /// must be handled.
+    pub future_states: OrderedHashMap<pre_sierra::LabelId, State>,
+}
+
+/// Same as [add_store_statements], with the behavior and the collected outputs determined by the
+/// given `config`.
+///
+/// Parameters that are marked as local variables are stored into their allocated space right
+/// after it is allocated, and are local variables from that point on.
+pub fn add_store_statements_with_config<'a, GetLibfuncSignature>(
+    db: &'a dyn SierraGenGroup,
+    statements: Vec<pre_sierra::Statement>,
+    get_lib_func_signature: &GetLibfuncSignature,
+    local_variables: LocalVariables,
+    params: &[sierra::program::Param],
+    config: StoreVariablesConfig<'a>,
+) -> Result<StoreOutput, StoreError>
+where
+    GetLibfuncSignature: Fn(ConcreteLibfuncId) -> LibfuncInfo,
+{
+    let passes = config.passes;
+    let cache_signatures = config.cache_signatures;
+    let local_vars = local_variables.keys().cloned().collect_vec();
+    let handler = AddStoreVariableStatements::new_with_config(db, local_variables, params, config);
+
+    let signatures = RefCell::default();
+    let get_cached_lib_func_signature = with_signature_cache(get_lib_func_signature, &signatures);
+    let get_cached_lib_func_signature = |libfunc_id: ConcreteLibfuncId| {
+        if cache_signatures {
+            get_cached_lib_func_signature(libfunc_id)
+        } else {
+            get_lib_func_signature(libfunc_id)
+        }
+    };
+    let mut handler = try_handle_statements(handler, statements, &get_cached_lib_func_signature)?;
+
+    let future_states = handler.drain_future_states();
+    let stats = handler.stats.take();
+    let used_locals = match &stats {
+        Some(stats) => local_vars
+            .into_iter()
+            .filter(|var| !stats.unused_local_variables.contains(var))
+            .collect(),
+        None => OrderedHashSet::default(),
+    };
+    let insertion_indices = match handler.insertion_indices.take() {
+        Some(indices) => (0..handler.result.len()).map(|idx| indices.get(&idx).copied()).collect(),
+        None => vec![],
+    };
+    let statements = passes.iter().fold(handler.result, |statements, pass| pass.run(statements));
+    Ok(StoreOutput {
+        statements,
+        comments: handler.comments.unwrap_or_default(),
+        used_locals,
+        stats: stats.unwrap_or_default(),
+        insertion_indices,
+        origins: handler.origins.unwrap_or_default(),
+        dispositions: handler.dispositions.unwrap_or_default(),
+        branch_stores: handler.branch_store_infos.unwrap_or_default(),
+        state_snapshots: handler.state_snapshots.unwrap_or_default(),
+        future_states,
+    })
+}
+
+/// Same as [add_store_statements], except that `get_lib_func_signature` is called at most once per
+/// libfunc, and the signatures of repeated libfuncs are cloned from a cache.
+///
+/// A shorthand for [add_store_statements_with_config] with
+/// [StoreVariablesConfig::cache_signatures]. See there for when it is worthwhile.
+pub fn add_store_statements_cached<GetLibfuncSignature>(
+    db: &dyn SierraGenGroup,
+    statements: Vec<pre_sierra::Statement>,
+    get_lib_func_signature: &GetLibfuncSignature,
+    local_variables: LocalVariables,
+    params: &[sierra::ids::VarId],
+) -> Vec<pre_sierra::Statement>
+where
+    GetLibfuncSignature: Fn(ConcreteLibfuncId) -> LibfuncInfo,
+{
+    let signatures = RefCell::default();
+    let get_cached_lib_func_signature = with_signature_cache(get_lib_func_signature, &signatures);
+    add_store_statements(db, statements, &get_cached_lib_func_signature, local_variables, params)
+}
+
+/// Returns a wrapper of `get_lib_func_signature` that fetches the signature of each libfunc at most
+/// once, and clones it from `signatures` afterwards.
+fn with_signature_cache<'b, GetLibfuncSignature>(
+    get_lib_func_signature: &'b GetLibfuncSignature,
+    signatures: &'b RefCell<UnorderedHashMap<ConcreteLibfuncId, LibfuncSignature>>,
+) -> impl Fn(ConcreteLibfuncId) -> LibfuncInfo + 'b
+where
+    GetLibfuncSignature: Fn(ConcreteLibfuncId) -> LibfuncInfo,
+{
+    move |libfunc_id: ConcreteLibfuncId| {
+        let signature = signatures
+            .borrow_mut()
+            .entry(libfunc_id.clone())
+            .or_insert_with(|| get_lib_func_signature(libfunc_id).signature)
+            .clone();
+        LibfuncInfo { signature }
+    }
+}
+
+/// Same as [add_store_statements], but also returns statistics about the added stores.
+///
+/// A shorthand for [add_store_statements_with_config] with [StoreVariablesConfig::collect_stats].
+pub fn add_store_statements_with_stats<GetLibfuncSignature>(
+    db: &dyn SierraGenGroup,
+    statements: Vec<pre_sierra::Statement>,
+    get_lib_func_signature: &GetLibfuncSignature,
+    local_variables: LocalVariables,
+    params: &[sierra::ids::VarId],
+) -> (Vec<pre_sierra::Statement>, StoreStats)
+where
+    GetLibfuncSignature: Fn(ConcreteLibfuncId) -> LibfuncInfo,
+{
+    let unused_local_variables = local_variables.keys().cloned().collect();
+    let mut handler =
+        AddStoreVariableStatements::new(db, local_variables, local_param_states(params));
+    handler.stats = Some(StoreStats { unused_local_variables, ..Default::default() });
+    let handler = handle_statements(handler, statements, get_lib_func_signature);
+    (handler.result, handler.stats.unwrap_or_default())
+}
+
+/// Same as [add_store_statements], but also returns a snapshot of the state after each of the
+/// given statements.
+///
+/// A shorthand for [add_store_statements_with_config] with
+/// [StoreVariablesConfig::collect_state_snapshots].
+pub fn add_store_statements_debug<GetLibfuncSignature>(
+    db: &dyn SierraGenGroup,
+    statements: Vec<pre_sierra::Statement>,
+    get_lib_func_signature: &GetLibfuncSignature,
+    local_variables: LocalVariables,
+    params: &[sierra::ids::VarId],
+) -> (Vec<pre_sierra::Statement>, Vec<StateSnapshot>)
+where
+    GetLibfuncSignature: Fn(ConcreteLibfuncId) -> LibfuncInfo,
+{
+    let mut handler =
+        AddStoreVariableStatements::new(db, local_variables, local_param_states(params));
+    handler.state_snapshots = Some(vec![]);
+    let handler = handle_statements(handler, statements, get_lib_func_signature);
+    (handler.result, handler.state_snapshots.unwrap_or_default())
+}
+
+/// Same as [add_store_statements], but also returns the used local variables and statistics about
+/// the added stores, collected in a single pass over the statements. The other outputs of the
+/// returned [StoreOutput] are empty.
+///
+/// As in [StoreOutput::used_locals], a local variable is used if a variable renamed from it,
+/// directly or through a chain of renames, is used.
+pub fn add_store_statements_full<GetLibfuncSignature>(
+    db: &dyn SierraGenGroup,
+    statements: Vec<pre_sierra::Statement>,
+    get_lib_func_signature: &GetLibfuncSignature,
+    local_variables: LocalVariables,
+    params: &[sierra::ids::VarId],
+) -> StoreOutput
+where
+    GetLibfuncSignature: Fn(ConcreteLibfuncId) -> LibfuncInfo,
+{
+    let local_vars = local_variables.keys().cloned().collect_vec();
+    let (statements, stats) = add_store_statements_with_stats(
+        db,
+        statements,
+        get_lib_func_signature,
+        local_variables,
+        params,
+    );
+    let used_locals =
+        local_vars.into_iter().filter(|var| !stats.unused_local_variables.contains(var)).collect();
+    StoreOutput { statements, used_locals, stats, ..Default::default() }
+}
+
+/// Statistics about the added stores. See [StoreVariablesConfig::collect_stats].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StoreStats {
+    /// A map from a libfunc to the number of stores caused by its signature, that is, stores of
+    /// deferred arguments that the libfunc does not accept.
+    ///
+    /// Stores of arguments that are marked as local variables, and stores forced by
+    /// [StoreVariablesConfig::force_store_libfuncs] where the signature allows the argument, are
+    /// not counted.
+    pub libfunc_store_counts: OrderedHashMap<ConcreteLibfuncId, usize>,
+    /// The variables marked as local variables that were never used, including through variables
+    /// renamed from them. The space allocated for them is wasted.
+    pub unused_local_variables: OrderedHashSet<sierra::ids::VarId>,
+    /// The maximal number of variables that are local variables at the same time, after any of the
+    /// statements. Parameters, which start as local variables, are counted as well.
+    ///
+    /// Unlike the number of added `store_local` statements, this accounts for local variables that
+    /// are no longer used.
+    pub peak_locals: usize,
+    /// A map from a type to the number of `store_temp` and `store_local` statements added for
+    /// variables of that type.
+    pub type_store_counts: OrderedHashMap<sierra::ids::ConcreteTypeId, usize>,
+    /// The number of pushed values that were not deferred and were already on the top of the
+    /// stack, in the order of the pushed values, and thus were not stored again.
+    pub push_renamed: usize,
+    /// The number of pushed values that were stored onto the stack from a temporary or a local
+    /// variable. This includes deferred values that were first stored as local variables.
+    pub push_restored: usize,
+    /// The number of the given statements.
+    pub input_statement_count: usize,
+    /// The number of the returned statements, including the added statements.
+    pub output_statement_count: usize,
+}
+impl StoreStats {
+    /// Returns the net number of statements added by the pass.
+    ///
+    /// Currently statements are only added, so the result is never negative.
+    pub fn statements_added(&self) -> isize {
+        self.output_statement_count as isize - self.input_statement_count as isize
+    }
+}
+
+/// The way a variable was handled by the added store statements.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Disposition {
+    /// The variable was stored as a temporary variable.
+    StoredTemp,
+    /// The variable was stored as a local variable.
+    StoredLocal,
+    /// The variable was consumed by a libfunc while deferred, without being stored.
+    ConsumedDeferred,
+}
+
+/// The stores added around a branching statement. See
+/// [StoreVariablesConfig::collect_branch_stores].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BranchStoreInfo {
+    /// The index of the branching statement in the given statements.
+    pub statement_idx: usize,
+    /// The number of stores added right before the branching statement. These are shared by all
+    /// of its branches.
+    pub stores_before_branch: usize,
+    /// The number of stores added on each of the branches, in the order of the branches.
+    ///
+    /// Merging the state of a branch into the state at its target never adds statements, so a
+    /// non-zero entry indicates a bug.
+    pub stores_per_branch: Vec<usize>,
+}
+
+/// Returns true if [add_store_statements] would add any store statement to the given statements.
+///
+/// Stops handling the statements once the first store statement is added.
+pub fn requires_stores<GetLibfuncSignature>(
+    db: &dyn SierraGenGroup,
+    statements: Vec<pre_sierra::Statement>,
+    get_lib_func_signature: &GetLibfuncSignature,
+    local_variables: LocalVariables,
+    params: &[sierra::ids::VarId],
+) -> bool
+where
+    GetLibfuncSignature: Fn(ConcreteLibfuncId) -> LibfuncInfo,
+{
+    let mut handler =
+        AddStoreVariableStatements::new(db, local_variables, local_param_states(params));
+    handler.stop_at_first_store = true;
+    handle_statements(handler, statements, get_lib_func_signature).store_count > 0
+}
+
+/// An error in adding the store statements.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum StoreError {
+    /// A statement uses a variable with an unknown state. Returned when using
+    /// [MissingVarPolicy::Error].
+    MissingVar {
+        /// The variable with the unknown state.
+        var: sierra::ids::VarId,
+        /// The index of the statement using the variable, in the given statements.
+        at_statement: usize,
+    },
+    /// More statements were added than allowed by [StoreVariablesConfig::max_inserted].
+    LimitExceeded {
+        /// The number of statements required so far, including the one exceeding the limit.
+        inserted: usize,
+        /// The maximal number of statements that may be added.
+        limit: usize,
+    },
+    /// A `dup` statement is required while [StoreVariablesConfig::forbid_dup] is set.
+    UnexpectedDup {
+        /// The variable that should have been duplicated.
+        var: sierra::ids::VarId,
+    },
+    /// A `store_temp` statement placed a value deeper in the known stack than allowed by
+    /// [StoreVariablesConfig::max_ap_offset].
+    ApOffsetExceeded {
+        /// The variable produced by the `store_temp` statement.
+        var: sierra::ids::VarId,
+        /// The depth of the known stack after the `store_temp` statement.
+        offset: usize,
+        /// The maximal depth of the known stack.
+        limit: usize,
+        /// The index of the statement before which the `store_temp` statement was added, in the
+        /// given statements.
+        at_statement: usize,
+    },
+}
+
+/// Runs `handler` on the given `statements` and returns it once all the statements are handled.
+///
+/// Panics if adding the store statements fails, e.g., if the limit of
+/// [StoreVariablesConfig::max_inserted] is exceeded.
+fn handle_statements<'a, GetLibfuncSignature>(
+    handler: AddStoreVariableStatements<'a>,
+    statements: Vec<pre_sierra::Statement>,
+    get_lib_func_signature: &GetLibfuncSignature,
+) -> AddStoreVariableStatements<'a>
+where
+    GetLibfuncSignature: Fn(ConcreteLibfuncId) -> LibfuncInfo,
+{
+    try_handle_statements(handler, statements, get_lib_func_signature)
+        .unwrap_or_else(|err| panic!("Failed to add store statements: {err:?}."))
+}
+
+/// Same as [handle_statements], except that errors are returned instead of causing a panic.
+///
+/// A statement using a variable with an unknown state is an error only if the handler uses
+/// [MissingVarPolicy::Error].
+fn try_handle_statements<'a, GetLibfuncSignature>(
+    mut handler: AddStoreVariableStatements<'a>,
+    statements: Vec<pre_sierra::Statement>,
+    get_lib_func_signature: &GetLibfuncSignature,
+) -> Result<AddStoreVariableStatements<'a>, StoreError>
+where
+    GetLibfuncSignature: Fn(ConcreteLibfuncId) -> LibfuncInfo,
+{
+    if handler.config.skip_unused_const_dup {
+        let mut remaining_uses = OrderedHashMap::<sierra::ids::VarId, usize>::default();
+        for statement in &statements {
+            for var in used_vars(statement) {
+                *remaining_uses.entry(var.clone()).or_insert(0) += 1;
+            }
+        }
+        handler.remaining_uses = Some(remaining_uses);
+    }
+    let input_statement_count = statements.len();
     // Go over the statements, restarting whenever we see a branch or a label.
-    for statement in statements.into_iter() {
-        handler.handle_statement(statement, get_lib_func_signature);
+    for (idx, statement) in statements.into_iter().enumerate() {
+        handler.statement_idx = idx;
+        handler.handle_statement(statement, get_lib_func_signature)?;
+        if let (true, Some(state)) = (handler.config.verify_invariants, &handler.state_opt) {
+            if let Err(divergent_vars) = state.verify_invariants() {
+                panic!(
+                    "The known stack is inconsistent after statement {idx}: {}.",
+                    divergent_vars.iter().join(", ")
+                );
+            }
+        }
+        if let (Some(stats), Some(state)) = (&mut handler.stats, &handler.state_opt) {
+            let live_locals =
+                state.variables.values().filter(|var_state| **var_state == VarState::LocalVar);
+            stats.peak_locals = stats.peak_locals.max(live_locals.count());
+        }
+        if let Some(state_snapshots) = &mut handler.state_snapshots {
+            state_snapshots.push(StateSnapshot::new(handler.state_opt.as_ref()));
+        }
+        if handler.stop_at_first_store && handler.store_count > 0 {
+            return Ok(handler);
+        }
+        if let Some(err) = handler.ap_offset_error.take() {
+            return Err(err);
+        }
+    }
+    handler.finalize();
+    if let Some(stats) = &mut handler.stats {
+        stats.input_statement_count = input_statement_count;
+        stats.output_statement_count = handler.result.len();
+    }
+    Ok(handler)
+}
+
+/// Returns the variables used by the given statement.
+fn used_vars(statement: &pre_sierra::Statement) -> Vec<&sierra::ids::VarId> {
+    match statement {
+        pre_sierra::Statement::Sierra(GenStatement::Invocation(invocation)) => {
+            invocation.args.iter().collect()
+        }
+        pre_sierra::Statement::Sierra(GenStatement::Return(vars)) => vars.iter().collect(),
+        pre_sierra::Statement::Label(_) => vec![],
+        pre_sierra::Statement::PushValues(push_values) => {
+            push_values.iter().map(|push_value| &push_value.var).collect()
+        }
+    }
+}
+
+/// Returns the initial states of the given parameters, where all of them are local variables.
+fn local_param_states(params: &[sierra::ids::VarId]) -> Vec<(sierra::ids::VarId, VarState)> {
+    params.iter().map(|var| (var.clone(), VarState::LocalVar)).collect()
+}
+
+/// The reason for adding a store statement.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StoreReason {
+    /// A deferred libfunc argument that is marked as a local variable.
+    LocalArgument,
+    /// A const libfunc argument, where the libfunc does not allow const arguments.
+    ConstArgumentNotAllowed,
+    /// An add-const libfunc argument, where the libfunc does not allow add-const arguments.
+    AddConstArgumentNotAllowed,
+    /// A deferred libfunc argument, where the libfunc does not allow deferred arguments.
+    DeferredArgumentNotAllowed,
+    /// A temporary libfunc argument that is marked as a local variable.
+    LocalTempArgument,
+    /// A variable that is pushed onto the stack.
+    PushValues,
+    /// A variable that may be lost due to branching.
+    Branch,
+    /// A variable that is marked as a local variable, before an unknown ap change.
+    UnknownApChange,
+    /// A deferred variable, stored due to [StorePolicy::StoreEagerly].
+    StoreEagerly,
+    /// A deferred libfunc argument, where the libfunc is forced to get stored arguments.
+    ForcedStore,
+}
+impl std::fmt::Display for StoreReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let reason = match self {
+            StoreReason::LocalArgument => "deferred argument marked as a local variable",
+            StoreReason::ConstArgumentNotAllowed => "const argument not allowed by libfunc",
+            StoreReason::AddConstArgumentNotAllowed => "add-const argument not allowed by libfunc",
+            StoreReason::DeferredArgumentNotAllowed => "deferred argument not allowed by libfunc",
+            StoreReason::LocalTempArgument => "temporary argument marked as a local variable",
+            StoreReason::PushValues => "pushing values onto the stack",
+            StoreReason::Branch => "variable may be lost by branching",
+            StoreReason::UnknownApChange => "local variable before an unknown ap change",
+            StoreReason::StoreEagerly => "deferred variable stored eagerly",
+            StoreReason::ForcedStore => "argument of a libfunc forced to get stored arguments",
+        };
+        write!(f, "{reason}")
     }
-    handler.finalize()
 }
 
 struct AddStoreVariableStatements<'a> {
@@ -74,16 +762,76 @@ struct AddStoreVariableStatements<'a> {
     /// added to the map. When the label is visited, it is merged with the known state, and removed
     /// from the map.
     future_states: OrderedHashMap<pre_sierra::LabelId, State>,
+    /// A map from [LabelId](pre_sierra::LabelId) to the number of branches that contributed a
+    /// state to the matching entry of `future_states`. Used for reporting unhandled labels.
+    future_states_branch_count: OrderedHashMap<pre_sierra::LabelId, usize>,
+    /// A map from the index of an added statement in `result` to a comment explaining why it was
+    /// added. None if comments are not collected.
+    comments: Option<OrderedHashMap<usize, String>>,
+    /// Statistics about the added stores. None if statistics are not collected.
+    stats: Option<StoreStats>,
+    /// A map from a renamed variable to the variable it was renamed from. Only collected with
+    /// `stats`.
+    renamed_from: OrderedHashMap<sierra::ids::VarId, sierra::ids::VarId>,
+    /// The snapshots of the state after each of the handled statements. None if snapshots are not
+    /// collected.
+    state_snapshots: Option<Vec<StateSnapshot>>,
+    /// A map from the allocated space of a parameter that is marked as a local variable, to the
+    /// parameter and its type. An entry is removed once the parameter is stored.
+    pending_local_params:
+        OrderedHashMap<sierra::ids::VarId, (sierra::ids::VarId, sierra::ids::ConcreteTypeId)>,
+    /// The configuration of the handler.
+    config: StoreVariablesConfig<'a>,
+    /// Parameters that are marked as local variables, whose space was allocated, and whose store
+    /// is delayed due to [StoreVariablesConfig::hoist_entry_locals]. Each entry contains the
+    /// allocated space, the parameter and its type.
+    hoisted_local_params:
+        Vec<(sierra::ids::VarId, (sierra::ids::VarId, sierra::ids::ConcreteTypeId))>,
+    /// The number of store statements added so far.
+    store_count: usize,
+    /// The number of statements added so far, including `dup` and `rename` statements.
+    inserted_count: usize,
+    /// A map from the index of an added statement in `result` to the number of statements that
+    /// were added before it. None if the indices are not collected.
+    insertion_indices: Option<OrderedHashMap<usize, usize>>,
+    /// A map from a variable produced by an added `store_temp` or `rename` statement to the
+    /// variable it originates from. None if the origins are not collected.
+    origins: Option<OrderedHashMap<sierra::ids::VarId, sierra::ids::VarId>>,
+    /// Whether to stop handling the statements once the first store statement is added.
+    stop_at_first_store: bool,
+    /// The index of the handled statement, in the given statements.
+    statement_idx: usize,
+    /// A map from a variable to the number of its uses that were not handled yet. None unless
+    /// [StoreVariablesConfig::skip_unused_const_dup] is set, as it is the only user of the map.
+    remaining_uses: Option<OrderedHashMap<sierra::ids::VarId, usize>>,
+    /// The stores added around each statement with more than one branch. None if they are not
+    /// collected.
+    branch_store_infos: Option<Vec<BranchStoreInfo>>,
+    /// A map from the allocated space of a local variable to the variable of
+    /// [StoreVariablesConfig::scratch_region] replacing it.
+    scratch_slots: OrderedHashMap<sierra::ids::VarId, sierra::ids::VarId>,
+    /// A map from a type to the number of variables of [StoreVariablesConfig::scratch_region] of
+    /// that type that were vended so far.
+    scratch_vended_counts: OrderedHashMap<sierra::ids::ConcreteTypeId, usize>,
+    /// Whether labels that are branched to and not handled are kept in `future_states` for another
+    /// segment of the function, instead of being an error.
+    keep_future_states: bool,
+    /// A map from a variable to the way it was handled so far. None if the dispositions are not
+    /// collected.
+    dispositions: Option<OrderedHashMap<sierra::ids::VarId, Disposition>>,
+    /// The first violation of [StoreVariablesConfig::max_ap_offset] in the handled statement, if
+    /// any. Returned once the statement is handled.
+    ap_offset_error: Option<StoreError>,
 }
 impl<'a> AddStoreVariableStatements<'a> {
     /// Constructs a new [AddStoreVariableStatements] object.
     fn new(
         db: &'a dyn SierraGenGroup,
         local_variables: LocalVariables,
-        params: &[sierra::ids::VarId],
+        param_states: Vec<(sierra::ids::VarId, VarState)>,
     ) -> Self {
         let mut state = State::default();
-        state.variables.extend(params.iter().map(|var| (var.clone(), VarState::LocalVar)));
+        state.variables.extend(param_states);
 
         AddStoreVariableStatements {
             db,
@@ -91,24 +839,132 @@ impl<'a> AddStoreVariableStatements<'a> {
             result: Vec::new(),
             state_opt: Some(state),
             future_states: OrderedHashMap::default(),
+            future_states_branch_count: OrderedHashMap::default(),
+            comments: None,
+            stats: None,
+            renamed_from: OrderedHashMap::default(),
+            state_snapshots: None,
+            pending_local_params: OrderedHashMap::default(),
+            config: StoreVariablesConfig::default(),
+            hoisted_local_params: vec![],
+            store_count: 0,
+            inserted_count: 0,
+            insertion_indices: None,
+            origins: None,
+            stop_at_first_store: false,
+            statement_idx: 0,
+            remaining_uses: None,
+            branch_store_infos: None,
+            scratch_slots: OrderedHashMap::default(),
+            scratch_vended_counts: OrderedHashMap::default(),
+            keep_future_states: false,
+            dispositions: None,
+            ap_offset_error: None,
         }
     }
 
+    /// Takes the states at the labels that were branched to and not handled.
+    fn drain_future_states(&mut self) -> OrderedHashMap<pre_sierra::LabelId, State> {
+        self.future_states_branch_count.clear();
+        std::mem::take(&mut self.future_states)
+    }
+
+    /// Constructs a new [AddStoreVariableStatements] object with the given `config`, where the
+    /// given `params` start as local variables.
+    fn new_with_config(
+        db: &'a dyn SierraGenGroup,
+        local_variables: LocalVariables,
+        params: &[sierra::program::Param],
+        mut config: StoreVariablesConfig<'a>,
+    ) -> Self {
+        let param_states =
+            local_param_states(&params.iter().map(|param| param.id.clone()).collect_vec());
+        let local_params = params
+            .iter()
+            .filter_map(|param| {
+                let uninitialized_local_var_id = local_variables.get(&param.id)?.clone();
+                Some((uninitialized_local_var_id, (param.id.clone(), param.ty.clone())))
+            })
+            .collect();
+        let mut handler = Self::new(db, local_variables, param_states);
+        handler.pending_local_params = local_params;
+        if config.collect_comments {
+            handler.comments = Some(OrderedHashMap::default());
+        }
+        if config.collect_stats {
+            let unused_local_variables = handler.local_variables.keys().cloned().collect();
+            handler.stats = Some(StoreStats { unused_local_variables, ..Default::default() });
+        }
+        if config.collect_insertion_indices {
+            handler.insertion_indices = Some(OrderedHashMap::default());
+        }
+        if config.collect_origins {
+            handler.origins = Some(OrderedHashMap::default());
+        }
+        if config.collect_dispositions {
+            handler.dispositions = Some(OrderedHashMap::default());
+        }
+        if config.collect_branch_stores {
+            handler.branch_store_infos = Some(vec![]);
+        }
+        if config.collect_state_snapshots {
+            handler.state_snapshots = Some(vec![]);
+        }
+        if config.starts_unreachable {
+            handler.state_opt = None;
+        }
+        if let Some(future_states) = config.future_states.take() {
+            handler.future_states_branch_count =
+                future_states.keys().map(|label_id| (*label_id, 1)).collect();
+            handler.future_states = future_states;
+            handler.keep_future_states = true;
+        }
+        handler.config = config;
+        handler
+    }
+
     /// Handles a single statement, including adding required store statements and the statement
     /// itself.
     fn handle_statement<GetLibfuncInfo>(
         &mut self,
         statement: pre_sierra::Statement,
         get_lib_func_signature: &GetLibfuncInfo,
-    ) where
+    ) -> Result<(), StoreError>
+    where
         GetLibfuncInfo: Fn(ConcreteLibfuncId) -> LibfuncInfo,
     {
+        let allocated_local_params = match &statement {
+            pre_sierra::Statement::Sierra(GenStatement::Invocation(invocation)) => {
+                self.take_allocated_local_params(invocation)
+            }
+            _ => vec![],
+        };
+        // The hoisted parameters are stored before the first statement that does not allocate the
+        // space of another parameter.
+        if allocated_local_params.is_empty() {
+            self.store_hoisted_local_params()?;
+        }
+        // The uses of a `PushValues` statement are handled one by one, in `push_values`.
+        if !matches!(statement, pre_sierra::Statement::PushValues(_)) {
+            for var in used_vars(&statement) {
+                self.handle_use(var);
+            }
+        }
         match &statement {
+            pre_sierra::Statement::Sierra(GenStatement::Invocation(invocation))
+                if self.config.skip_redundant_store_temp
+                    && self.is_redundant_store_temp(invocation) =>
+            {
+                // The statement repeats the preceding one, and is dropped.
+            }
             pre_sierra::Statement::Sierra(GenStatement::Invocation(invocation)) => {
                 let libfunc_info = get_lib_func_signature(invocation.libfunc_id.clone());
                 let signature = libfunc_info.signature;
-                let arg_states =
-                    self.prepare_libfunc_arguments(&invocation.args, &signature.param_signatures);
+                let arg_states = self.prepare_libfunc_arguments(
+                    &invocation.libfunc_id,
+                    &invocation.args,
+                    &signature.param_signatures,
+                )?;
                 match &invocation.branches[..] {
                     [GenBranchInfo { target: GenBranchTarget::Fallthrough, results }] => {
                         // A simple invocation.
@@ -117,7 +973,9 @@ impl<'a> AddStoreVariableStatements<'a> {
                             SierraApChange::Unknown => {
                                 // If the ap-change is unknown, variables that will be revoked
                                 // otherwise should be stored as locals.
-                                self.store_variables_as_locals();
+                                self.with_reason(StoreReason::UnknownApChange, |this| {
+                                    this.store_variables_as_locals()
+                                })?;
                             }
                             SierraApChange::BranchAlign | SierraApChange::Known { .. } => {}
                         }
@@ -131,9 +989,15 @@ impl<'a> AddStoreVariableStatements<'a> {
                     }
                     _ => {
                         // This starts a branch. Store all deferred variables.
-                        if invocation.branches.len() > 1 {
-                            self.store_all_possibly_lost_variables();
+                        let is_multi_branch = invocation.branches.len() > 1;
+                        let store_count_before_branch = self.store_count;
+                        if is_multi_branch {
+                            self.with_reason(StoreReason::Branch, |this| {
+                                this.store_all_possibly_lost_variables()
+                            })?;
                         }
+                        let stores_before_branch = self.store_count - store_count_before_branch;
+                        let mut stores_per_branch = vec![];
 
                         // Go over the branches. The state of a branch that points to `Fallthrough`
                         // is merged into `fallthrough_state`.
@@ -149,16 +1013,31 @@ impl<'a> AddStoreVariableStatements<'a> {
                                 &arg_states,
                             );
 
+                            let store_count_before_edge = self.store_count;
                             self.add_future_state(
                                 &branch.target,
                                 state_at_branch,
                                 &mut fallthrough_state,
                             );
+                            stores_per_branch.push(self.store_count - store_count_before_edge);
                         }
                         self.state_opt = fallthrough_state;
+                        if let Some(branch_store_infos) = &mut self.branch_store_infos {
+                            if is_multi_branch {
+                                branch_store_infos.push(BranchStoreInfo {
+                                    statement_idx: self.statement_idx,
+                                    stores_before_branch,
+                                    stores_per_branch,
+                                });
+                            }
+                        }
                     }
                 }
                 self.result.push(statement);
+                self.hoisted_local_params.extend(allocated_local_params);
+                if !self.config.hoist_entry_locals || self.pending_local_params.is_empty() {
+                    self.store_hoisted_local_params()?;
+                }
             }
             pre_sierra::Statement::Sierra(GenStatement::Return(_return_statement)) => {
                 self.result.push(statement);
@@ -178,90 +1057,170 @@ impl<'a> AddStoreVariableStatements<'a> {
                     std::mem::take(&mut self.state_opt),
                     self.future_states.swap_remove(label_id),
                 );
+                self.future_states_branch_count.swap_remove(label_id);
 
                 self.result.push(statement);
             }
             pre_sierra::Statement::PushValues(push_values) => {
-                self.push_values(push_values);
+                self.with_reason(StoreReason::PushValues, |this| this.push_values(push_values))?;
             }
         }
+        if self.config.store_policy == StorePolicy::StoreEagerly && self.state_opt.is_some() {
+            self.with_reason(StoreReason::StoreEagerly, |this| this.store_deferred_variables())?;
+        }
+        Ok(())
     }
 
-    /// Prepares the given `args` to be used as arguments for a libfunc.
+    /// Returns the parameters whose allocated space is an output of `invocation`, together with the
+    /// allocated space and their type, and removes them from `pending_local_params`.
+    fn take_allocated_local_params(
+        &mut self,
+        invocation: &GenInvocation<pre_sierra::LabelId>,
+    ) -> Vec<(sierra::ids::VarId, (sierra::ids::VarId, sierra::ids::ConcreteTypeId))> {
+        if self.pending_local_params.is_empty() {
+            return vec![];
+        }
+        invocation
+            .branches
+            .iter()
+            .flat_map(|branch| &branch.results)
+            .filter_map(|var| Some((var.clone(), self.pending_local_params.swap_remove(var)?)))
+            .collect()
+    }
+
+    /// Stores the parameters in `hoisted_local_params` into their allocated space.
+    fn store_hoisted_local_params(&mut self) -> Result<(), StoreError> {
+        for (uninitialized_local_var_id, (var, ty)) in
+            std::mem::take(&mut self.hoisted_local_params)
+        {
+            self.store_local(&var, &uninitialized_local_var_id, &ty)?;
+        }
+        Ok(())
+    }
+
+    /// Marks that one of the uses of `var` was handled.
+    fn handle_use(&mut self, var: &sierra::ids::VarId) {
+        if let Some(count) = self.remaining_uses.as_mut().and_then(|uses| uses.get_mut(var)) {
+            *count -= 1;
+        }
+    }
+
+    /// Returns true if `invocation` is a `store_temp` of a temporary variable into itself, that
+    /// repeats the immediately preceding statement, and may therefore be skipped.
+    ///
+    /// Since a label is pushed to `result`, this never matches across a label.
+    fn is_redundant_store_temp(&mut self, invocation: &GenInvocation<pre_sierra::LabelId>) -> bool {
+        let Some(pre_sierra::Statement::Sierra(GenStatement::Invocation(prev_invocation))) =
+            self.result.last()
+        else {
+            return false;
+        };
+        if prev_invocation != invocation {
+            return false;
+        }
+        let ([var], [GenBranchInfo { target: GenBranchTarget::Fallthrough, results }]) =
+            (&invocation.args[..], &invocation.branches[..])
+        else {
+            return false;
+        };
+        if results[..] != [var.clone()] {
+            return false;
+        }
+        let Some(VarState::TempVar { ty }) = self.state_ref().variables.get(var).cloned() else {
+            return false;
+        };
+        invocation.libfunc_id == self.config.libfunc_provider.store_temp_libfunc_id(self.db, ty)
+            && self.known_stack().get(var) == Some(-1)
+    }
+
+    /// Prepares the given `args` to be used as arguments for the libfunc `libfunc_id`.
     ///
     /// Returns a map from arguments' [sierra::ids::VarId] to [DeferredVariableInfo] for arguments
     /// that have a deferred value after the function (that is, they were not stored as
     /// temp/local by the function).
     fn prepare_libfunc_arguments(
         &mut self,
+        libfunc_id: &ConcreteLibfuncId,
         args: &[sierra::ids::VarId],
         param_signatures: &[ParamSignature],
-    ) -> Vec<VarState> {
+    ) -> Result<Vec<VarState>, StoreError> {
         zip_eq(args, param_signatures)
             .map(|(arg, param_signature)| {
                 let arg_state = self.prepare_libfunc_argument(
+                    libfunc_id,
                     arg,
                     param_signature.allow_deferred,
                     param_signature.allow_add_const,
                     param_signature.allow_const,
-                );
+                )?;
+                if matches!(arg_state, VarState::Deferred { .. }) {
+                    self.record_disposition(arg, Disposition::ConsumedDeferred);
+                }
                 // Make sure the argument is consumed.
                 self.state().variables.swap_remove(arg);
-                arg_state
+                Ok(arg_state)
             })
             .collect()
     }
 
-    /// Prepares the given `arg` to be used as an argument for a libfunc.
+    /// Prepares the given `arg` to be used as an argument for the libfunc `libfunc_id`.
     ///
     /// Returns the VarState of the argument.
     fn prepare_libfunc_argument(
         &mut self,
+        libfunc_id: &ConcreteLibfuncId,
         arg: &sierra::ids::VarId,
         allow_deferred: bool,
         allow_add_const: bool,
         allow_const: bool,
-    ) -> VarState {
-        let var_state = self.state().variables.swap_remove(arg).unwrap_or_else(|| {
-            unreachable!("Unknown state for variable `{arg}`.");
-        });
-        match &var_state {
+    ) -> Result<VarState, StoreError> {
+        let var_state = self.take_var_state(arg)?;
+        Ok(match &var_state {
             VarState::Deferred { info: deferred_info } => {
-                if self.local_variables.get(arg).is_some() {
+                if self.local_variable_slot(arg).is_some() {
                     // If a deferred argument was marked as a local variable, then store
                     // it. This is important in case an alias of the variable is used later
                     // (for example, due to `SameAsParam` output).
-                    self.store_deferred(arg, &deferred_info.ty)
+                    self.with_reason(StoreReason::LocalArgument, |this| {
+                        this.store_deferred(arg, &deferred_info.ty)
+                    })?
                 } else {
-                    match deferred_info.kind {
-                        state::DeferredVariableKind::Const => {
-                            if !allow_const {
-                                return self.store_deferred(arg, &deferred_info.ty);
-                            }
+                    let reason = match deferred_info.kind {
+                        state::DeferredVariableKind::Const if !allow_const => {
+                            StoreReason::ConstArgumentNotAllowed
                         }
-                        state::DeferredVariableKind::AddConst => {
-                            if !allow_add_const {
-                                return self.store_deferred(arg, &deferred_info.ty);
-                            }
+                        state::DeferredVariableKind::AddConst if !allow_add_const => {
+                            StoreReason::AddConstArgumentNotAllowed
                         }
-                        state::DeferredVariableKind::Generic => {
-                            if !allow_deferred {
-                                return self.store_deferred(arg, &deferred_info.ty);
-                            }
+                        state::DeferredVariableKind::Generic if !allow_deferred => {
+                            StoreReason::DeferredArgumentNotAllowed
                         }
+                        _ if self.config.force_store_libfuncs.contains(libfunc_id) => {
+                            StoreReason::ForcedStore
+                        }
+                        _ => return Ok(var_state),
                     };
-                    var_state
+                    // Forced stores are caused by the config rather than by the signature.
+                    if reason != StoreReason::ForcedStore {
+                        if let Some(stats) = &mut self.stats {
+                            *stats.libfunc_store_counts.entry(libfunc_id.clone()).or_insert(0) += 1;
+                        }
+                    }
+                    self.with_reason(reason, |this| this.store_deferred(arg, &deferred_info.ty))?
                 }
             }
             VarState::TempVar { .. } => {
                 self.state().variables.insert(arg.clone(), var_state.clone());
-                if self.store_temp_as_local(arg) {
-                    return VarState::LocalVar;
+                let stored_as_local = self.with_reason(StoreReason::LocalTempArgument, |this| {
+                    this.store_temp_as_local(arg)
+                })?;
+                if stored_as_local {
+                    return Ok(VarState::LocalVar);
                 }
                 var_state
             }
             VarState::LocalVar => VarState::LocalVar,
-        }
+        })
     }
 
     /// Adds a store_temp() or store_local() instruction for the given deferred variable.
@@ -272,7 +1231,7 @@ impl<'a> AddStoreVariableStatements<'a> {
         &mut self,
         var: &sierra::ids::VarId,
         ty: &sierra::ids::ConcreteTypeId,
-    ) -> VarState {
+    ) -> Result<VarState, StoreError> {
         self.store_deferred_ex(var, var, ty)
     }
 
@@ -282,51 +1241,59 @@ impl<'a> AddStoreVariableStatements<'a> {
         var: &sierra::ids::VarId,
         var_on_stack: &sierra::ids::VarId,
         ty: &sierra::ids::ConcreteTypeId,
-    ) -> VarState {
+    ) -> Result<VarState, StoreError> {
         // Check if this variable should be a local variable.
-        if let Some(uninitialized_local_var_id) = self.local_variables.get(var) {
-            self.store_local(var, &uninitialized_local_var_id.clone(), ty);
+        Ok(if let Some(uninitialized_local_var_id) = self.local_variable_slot(var) {
+            self.store_local(var, &uninitialized_local_var_id, ty)?;
+            VarState::LocalVar
+        } else if let Some(scratch_var) = self.preferred_local_slot(var, ty) {
+            self.store_local_into(var, &scratch_var, ty)?;
             VarState::LocalVar
         } else {
-            self.store_temp(var, var_on_stack, ty);
+            self.store_temp(var, var_on_stack, ty)?;
             VarState::TempVar { ty: ty.clone() }
-        }
+        })
     }
 
-    fn push_values(&mut self, push_values: &Vec<pre_sierra::PushValue>) {
+    fn push_values(&mut self, push_values: &Vec<pre_sierra::PushValue>) -> Result<(), StoreError> {
         if push_values.is_empty() {
-            return;
+            return Ok(());
         }
 
         // Optimization: check if there is a prefix of `push_values` that is already on the stack.
-        let prefix_size = self.known_stack().compute_on_stack_prefix_size(push_values);
+        let prefix_size = if self.config.disable_push_prefix_opt {
+            0
+        } else {
+            self.known_stack().compute_on_stack_prefix_size(push_values)
+        };
 
         for (i, pre_sierra::PushValue { var, var_on_stack, ty, dup }) in
             push_values.iter().enumerate()
         {
-            let var_state = self
-                .state()
-                .variables
-                .swap_remove(var)
-                .unwrap_or_else(|| unreachable!("Unknown state for variable `{var}`."));
+            self.handle_use(var);
+            let var_state = self.take_var_state(var)?;
 
             let is_on_stack = if let VarState::Deferred { info: deferred_info } = &var_state {
                 let deferred_info = deferred_info.clone();
                 if let DeferredVariableKind::Const = deferred_info.kind {
                     // TODO(orizi): This is an ugly fix for case of literals. Fix properly.
-                    if *dup {
-                        self.dup(var, var_on_stack, ty);
-                        self.store_temp(var_on_stack, var_on_stack, ty);
+                    let is_unused = self
+                        .remaining_uses
+                        .as_ref()
+                        .is_some_and(|uses| uses.get(var).copied().unwrap_or_default() == 0);
+                    if *dup && !is_unused {
+                        self.dup(var, var_on_stack, ty)?;
+                        self.store_temp(var_on_stack, var_on_stack, ty)?;
                         self.state().variables.insert(
                             var.clone(),
                             VarState::Deferred { info: deferred_info.clone() },
                         );
                     } else {
-                        self.store_temp(var, var_on_stack, ty);
+                        self.store_temp(var, var_on_stack, ty)?;
                     }
                     continue;
                 } else if matches!(
-                    self.store_deferred_ex(var, var_on_stack, &deferred_info.ty),
+                    self.store_deferred_ex(var, var_on_stack, &deferred_info.ty)?,
                     VarState::TempVar { .. }
                 ) {
                     if *dup {
@@ -335,7 +1302,7 @@ impl<'a> AddStoreVariableStatements<'a> {
                         self.state()
                             .variables
                             .insert(var.clone(), VarState::TempVar { ty: ty.clone() });
-                        self.dup(var_on_stack, var, ty);
+                        self.dup(var_on_stack, var, ty)?;
                     }
                     continue;
                 } else {
@@ -349,71 +1316,158 @@ impl<'a> AddStoreVariableStatements<'a> {
                 i < prefix_size
             };
 
+            if let Some(stats) = &mut self.stats {
+                if is_on_stack {
+                    stats.push_renamed += 1;
+                } else {
+                    stats.push_restored += 1;
+                }
+            }
             if is_on_stack {
                 if *dup {
                     self.state().variables.insert(var_on_stack.clone(), var_state);
-                    self.dup(var, var_on_stack, ty);
+                    self.dup(var, var_on_stack, ty)?;
                 } else {
-                    self.rename_var(var, var_on_stack, ty);
+                    self.rename_var(var, var_on_stack, ty)?;
                 }
             } else {
                 let src = if *dup {
-                    self.dup(var, var_on_stack, ty);
+                    self.dup(var, var_on_stack, ty)?;
                     var_on_stack
                 } else {
                     var
                 };
-                self.store_temp(src, var_on_stack, ty);
+                self.store_temp(src, var_on_stack, ty)?;
             }
         }
+        Ok(())
     }
 
     /// Stores all the variables that may possibly get misaligned or revoked.
-    fn store_all_possibly_lost_variables(&mut self) {
+    ///
+    /// With [StorePolicy::StoreAllBeforeBranch], stores the rest of the deferred and temporary
+    /// variables as well, so that all the variables are either local variables or on the known
+    /// stack.
+    fn store_all_possibly_lost_variables(&mut self) -> Result<(), StoreError> {
+        let store_all = self.config.store_policy == StorePolicy::StoreAllBeforeBranch;
         for (var, var_state) in self.state().variables.clone() {
             match var_state {
-                VarState::TempVar { .. } => {
-                    self.store_temp_as_local(&var);
+                VarState::TempVar { ty } => {
+                    if !self.store_temp_as_local(&var)?
+                        && store_all
+                        && self.known_stack().get(&var).is_none()
+                    {
+                        self.store_temp(&var, &var, &ty)?;
+                    }
                 }
                 VarState::Deferred { info } => {
-                    if info.kind != DeferredVariableKind::Const {
+                    if store_all || info.kind != DeferredVariableKind::Const {
                         self.state().variables.swap_remove(&var);
-                        self.store_deferred(&var, &info.ty);
+                        self.store_deferred(&var, &info.ty)?;
                     }
                 }
                 VarState::LocalVar => {}
             }
         }
+        Ok(())
+    }
+
+    /// Stores all the deferred variables.
+    fn store_deferred_variables(&mut self) -> Result<(), StoreError> {
+        for (var, var_state) in self.state().variables.clone() {
+            if let VarState::Deferred { info } = var_state {
+                self.state().variables.swap_remove(&var);
+                self.store_deferred(&var, &info.ty)?;
+            }
+        }
+        Ok(())
     }
 
     /// Copies the given variable into a local variable if it is marked as local.
     /// Removes it from [State::variables].
-    fn store_temp_as_local(&mut self, var: &sierra::ids::VarId) -> bool {
-        if let Some(uninitialized_local_var_id) = self.local_variables.get(var).cloned() {
-            let var_state = self.state().variables.swap_remove(var).unwrap();
+    fn store_temp_as_local(&mut self, var: &sierra::ids::VarId) -> Result<bool, StoreError> {
+        if let Some(uninitialized_local_var_id) = self.local_variable_slot(var) {
+            let var_state = self.take_var_state(var)?;
 
             let VarState::TempVar { ty } = var_state else {
                 panic!("Expected a temporary variable");
             };
-            self.store_local(var, &uninitialized_local_var_id, &ty);
-            return true;
+            self.store_local(var, &uninitialized_local_var_id, &ty)?;
+            return Ok(true);
+        }
+        if let Some(VarState::TempVar { ty }) = self.state().variables.get(var).cloned() {
+            if let Some(scratch_var) = self.preferred_local_slot(var, &ty) {
+                self.take_var_state(var)?;
+                self.store_local_into(var, &scratch_var, &ty)?;
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Returns the next unused variable of the scratch region of type `ty`, if `prefer_local` is
+    /// given and prefers `var` to be stored as a local variable.
+    fn preferred_local_slot(
+        &mut self,
+        var: &sierra::ids::VarId,
+        ty: &sierra::ids::ConcreteTypeId,
+    ) -> Option<sierra::ids::VarId> {
+        let prefer_local = self.config.prefer_local.as_mut()?;
+        if !prefer_local(var, ty) {
+            return None;
+        }
+        self.vend_scratch_var(ty)
+    }
+
+    /// Returns the allocated space of the given variable, if it is marked as a local variable.
+    fn local_variable_slot(&self, var: &sierra::ids::VarId) -> Option<sierra::ids::VarId> {
+        self.local_variables.get(var).cloned()
+    }
+
+    /// If statistics are collected, marks the given variable, and the variables it was renamed
+    /// from, as used local variables.
+    fn mark_local_variable_used(&mut self, var: &sierra::ids::VarId) {
+        if let Some(stats) = &mut self.stats {
+            let mut var = var;
+            loop {
+                stats.unused_local_variables.shift_remove(var);
+                let Some(src) = self.renamed_from.get(var) else {
+                    break;
+                };
+                var = src;
+            }
+        }
+    }
+
+    /// Removes the given variable from [State::variables] and returns its state.
+    ///
+    /// If the state of the variable is unknown, panics or returns an error, according to
+    /// [StoreVariablesConfig::missing_var_policy].
+    fn take_var_state(&mut self, var: &sierra::ids::VarId) -> Result<VarState, StoreError> {
+        if let Some(var_state) = self.state().variables.swap_remove(var) {
+            return Ok(var_state);
+        }
+        match self.config.missing_var_policy {
+            MissingVarPolicy::Panic => unreachable!("Unknown state for variable `{var}`."),
+            MissingVarPolicy::Error => {
+                Err(StoreError::MissingVar { var: var.clone(), at_statement: self.statement_idx })
+            }
         }
-        false
     }
 
     /// Stores all the deffered and temporary variables as local variables.
-    fn store_variables_as_locals(&mut self) {
+    fn store_variables_as_locals(&mut self) -> Result<(), StoreError> {
         let mut vars_to_store: Vec<(
             sierra::ids::VarId,
             sierra::ids::VarId,
             sierra::ids::ConcreteTypeId,
         )> = vec![];
-        for (var, var_state) in self.state_ref().variables.iter() {
-            if let Some(uninitialized_local_var_id) = self.local_variables.get(var).cloned() {
+        for (var, var_state) in self.state_ref().variables.clone() {
+            if let Some(uninitialized_local_var_id) = self.local_variable_slot(&var) {
                 match var_state {
                     VarState::Deferred { info: DeferredVariableInfo { ty, .. } }
                     | VarState::TempVar { ty } => {
-                        vars_to_store.push((var.clone(), uninitialized_local_var_id, ty.clone()))
+                        vars_to_store.push((var, uninitialized_local_var_id, ty))
                     }
                     VarState::LocalVar => {}
                 };
@@ -422,20 +1476,80 @@ impl<'a> AddStoreVariableStatements<'a> {
 
         for (var, uninitialized_local_var_id, ty) in vars_to_store {
             assert!(self.state().variables.swap_remove(&var).is_some());
-            self.store_local(&var, &uninitialized_local_var_id, &ty);
+            self.store_local(&var, &uninitialized_local_var_id, &ty)?;
         }
+        Ok(())
     }
 
-    fn finalize(self) -> Vec<pre_sierra::Statement> {
+    /// Checks that all the statements were handled properly.
+    fn finalize(&self) {
         assert!(
             self.state_opt.is_none(),
             "Internal compiler error: Found a reachable statement at the end of the function."
         );
-        assert!(
-            self.future_states.is_empty(),
-            "Internal compiler error: Unhandled label in 'store_variables'."
-        );
-        self.result
+        if !self.keep_future_states && !self.future_states.is_empty() {
+            let unhandled_labels = self
+                .future_states
+                .keys()
+                .map(|label_id| {
+                    let branch_count = self.future_states_branch_count[*label_id];
+                    format!(
+                        "{} ({branch_count} branch{})",
+                        label_id.with_db(self.db),
+                        if branch_count == 1 { "" } else { "es" }
+                    )
+                })
+                .join(", ");
+            panic!(
+                "Internal compiler error: Unhandled labels in 'store_variables': \
+                 {unhandled_labels}."
+            );
+        }
+    }
+
+    /// Runs `f`, and if comments are collected, attaches `reason` to every statement added by it.
+    fn with_reason<T>(&mut self, reason: StoreReason, f: impl FnOnce(&mut Self) -> T) -> T {
+        let start = self.result.len();
+        let res = f(self);
+        if let Some(comments) = &mut self.comments {
+            for idx in start..self.result.len() {
+                comments.insert(idx, reason.to_string());
+            }
+        }
+        res
+    }
+
+    /// Adds a statement that was not part of the given statements to the result.
+    ///
+    /// Returns [StoreError::LimitExceeded] instead if [StoreVariablesConfig::max_inserted] is
+    /// reached.
+    fn push_inserted(&mut self, statement: pre_sierra::Statement) -> Result<(), StoreError> {
+        if let Some(limit) = self.config.max_inserted {
+            if self.inserted_count >= limit {
+                return Err(StoreError::LimitExceeded { inserted: self.inserted_count + 1, limit });
+            }
+        }
+        if let Some(insertion_indices) = &mut self.insertion_indices {
+            insertion_indices.insert(self.result.len(), self.inserted_count);
+        }
+        self.inserted_count += 1;
+        self.result.push(statement);
+        Ok(())
+    }
+
+    /// Records that `dst` originates from `src`, if the origins are collected.
+    fn record_origin(&mut self, src: &sierra::ids::VarId, dst: &sierra::ids::VarId) {
+        if let Some(origins) = &mut self.origins {
+            let origin = origins.get(src).unwrap_or(src).clone();
+            origins.insert(dst.clone(), origin);
+        }
+    }
+
+    /// Records the way `var` was handled, if the dispositions are collected.
+    fn record_disposition(&mut self, var: &sierra::ids::VarId, disposition: Disposition) {
+        if let Some(dispositions) = &mut self.dispositions {
+            dispositions.insert(var.clone(), disposition);
+        }
     }
 
     /// Adds a `store_temp` command storing `var` into `var_on_stack`.
@@ -444,45 +1558,118 @@ impl<'a> AddStoreVariableStatements<'a> {
         var: &sierra::ids::VarId,
         var_on_stack: &sierra::ids::VarId,
         ty: &sierra::ids::ConcreteTypeId,
-    ) {
-        self.result.push(simple_statement(
-            store_temp_libfunc_id(self.db, ty.clone()),
+    ) -> Result<(), StoreError> {
+        self.store_count += 1;
+        self.count_type_store(ty);
+        self.push_inserted(simple_statement(
+            self.config.libfunc_provider.store_temp_libfunc_id(self.db, ty.clone()),
             &[var.clone()],
             &[var_on_stack.clone()],
-        ));
+        ))?;
+        self.record_origin(var, var_on_stack);
+        self.record_disposition(var, Disposition::StoredTemp);
 
         self.known_stack().push(var_on_stack);
         self.state().variables.insert(var_on_stack.clone(), VarState::TempVar { ty: ty.clone() });
+        if let (Some(limit), None) = (self.config.max_ap_offset, &self.ap_offset_error) {
+            let offset = self.known_stack().depth();
+            if offset > limit {
+                self.ap_offset_error = Some(StoreError::ApOffsetExceeded {
+                    var: var_on_stack.clone(),
+                    offset,
+                    limit,
+                    at_statement: self.statement_idx,
+                });
+            }
+        }
+        Ok(())
     }
 
     /// Adds a `store_local` command storing `var` into itself using the preallocated
-    /// `uninitialized_local_var_id`.
+    /// `uninitialized_local_var_id`, or the variable of the scratch region replacing it.
     fn store_local(
         &mut self,
         var: &sierra::ids::VarId,
         uninitialized_local_var_id: &sierra::ids::VarId,
         ty: &sierra::ids::ConcreteTypeId,
-    ) {
-        self.result.push(simple_statement(
-            store_local_libfunc_id(self.db, ty.clone()),
+    ) -> Result<(), StoreError> {
+        self.mark_local_variable_used(var);
+        let scratch_slot = self.scratch_slot(uninitialized_local_var_id, ty);
+        let uninitialized_local_var_id =
+            scratch_slot.as_ref().unwrap_or(uninitialized_local_var_id);
+        self.store_local_into(var, uninitialized_local_var_id, ty)
+    }
+
+    /// Adds a `store_local` command storing `var` into `uninitialized_local_var_id` as is,
+    /// without replacing it with a variable of the scratch region.
+    fn store_local_into(
+        &mut self,
+        var: &sierra::ids::VarId,
+        uninitialized_local_var_id: &sierra::ids::VarId,
+        ty: &sierra::ids::ConcreteTypeId,
+    ) -> Result<(), StoreError> {
+        self.store_count += 1;
+        self.count_type_store(ty);
+        self.push_inserted(simple_statement(
+            self.config.libfunc_provider.store_local_libfunc_id(self.db, ty.clone()),
             &[uninitialized_local_var_id.clone(), var.clone()],
             &[var.clone()],
-        ));
+        ))?;
         self.state().variables.insert(var.clone(), VarState::LocalVar);
+        self.record_disposition(var, Disposition::StoredLocal);
+        Ok(())
+    }
+
+    /// Counts a store of a variable of type `ty`, if statistics are collected.
+    fn count_type_store(&mut self, ty: &sierra::ids::ConcreteTypeId) {
+        if let Some(stats) = &mut self.stats {
+            *stats.type_store_counts.entry(ty.clone()).or_insert(0) += 1;
+        }
+    }
+
+    /// Returns the variable of the scratch region replacing the allocated space `slot` of a local
+    /// variable of type `ty`, or None if there is no scratch region, or it has no unused variable
+    /// of type `ty`.
+    fn scratch_slot(
+        &mut self,
+        slot: &sierra::ids::VarId,
+        ty: &sierra::ids::ConcreteTypeId,
+    ) -> Option<sierra::ids::VarId> {
+        if let Some(scratch_slot) = self.scratch_slots.get(slot) {
+            return Some(scratch_slot.clone());
+        }
+        let scratch_slot = self.vend_scratch_var(ty)?;
+        self.scratch_slots.insert(slot.clone(), scratch_slot.clone());
+        Some(scratch_slot)
+    }
+
+    /// Returns the next unused variable of the scratch region of type `ty` and marks it as used, or
+    /// None if there is no scratch region, or it has no unused variable of type `ty`.
+    fn vend_scratch_var(&mut self, ty: &sierra::ids::ConcreteTypeId) -> Option<sierra::ids::VarId> {
+        let scratch_region = self.config.scratch_region?;
+        let vended_count = self.scratch_vended_counts.entry(ty.clone()).or_insert(0);
+        let scratch_var = scratch_region.slots.get(ty)?.get(*vended_count)?.clone();
+        *vended_count += 1;
+        Some(scratch_var)
     }
 
     /// Adds a call to the dup() libfunc, duplicating `var` into `dup_var`.
+    ///
+    /// Returns [StoreError::UnexpectedDup] if [StoreVariablesConfig::forbid_dup] is set.
     fn dup(
         &mut self,
         var: &sierra::ids::VarId,
         dup_var: &sierra::ids::VarId,
         ty: &sierra::ids::ConcreteTypeId,
-    ) {
-        self.result.push(simple_statement(
+    ) -> Result<(), StoreError> {
+        if self.config.forbid_dup {
+            return Err(StoreError::UnexpectedDup { var: var.clone() });
+        }
+        self.push_inserted(simple_statement(
             dup_libfunc_id(self.db, ty.clone()),
             &[var.clone()],
             &[var.clone(), dup_var.clone()],
-        ));
+        ))
     }
 
     /// Adds a call to the rename() libfunc, renaming `src` to `dst`.
@@ -491,14 +1678,19 @@ impl<'a> AddStoreVariableStatements<'a> {
         src: &sierra::ids::VarId,
         dst: &sierra::ids::VarId,
         ty: &sierra::ids::ConcreteTypeId,
-    ) {
-        self.result.push(simple_statement(
+    ) -> Result<(), StoreError> {
+        self.push_inserted(simple_statement(
             rename_libfunc_id(self.db, ty.clone()),
             &[src.clone()],
             &[dst.clone()],
-        ));
+        ))?;
 
         self.state().rename_var(src, dst);
+        self.record_origin(src, dst);
+        if self.stats.is_some() {
+            self.renamed_from.insert(dst.clone(), src.clone());
+        }
+        Ok(())
     }
 
     /// Returns the current state, assuming the current statement is reachable.
@@ -538,6 +1730,7 @@ impl<'a> AddStoreVariableStatements<'a> {
                 let new_state =
                     merge_optional_states(self.future_states.swap_remove(label_id), Some(state));
                 self.future_states.insert(*label_id, extract_matches!(new_state, Some));
+                *self.future_states_branch_count.entry(*label_id).or_insert(0) += 1;
             }
         }
     }
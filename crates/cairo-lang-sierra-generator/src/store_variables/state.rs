@@ -1,16 +1,24 @@
+#[cfg(test)]
+#[path = "state_test.rs"]
+mod test;
+
 use cairo_lang_sierra as sierra;
 use cairo_lang_sierra::extensions::lib_func::{
     BranchSignature, DeferredOutputKind, OutputVarInfo, SierraApChange,
 };
 use cairo_lang_sierra::extensions::OutputVarReferenceInfo;
 use cairo_lang_utils::casts::IntoOrPanic;
-use cairo_lang_utils::ordered_hash_map::OrderedHashMap;
+use cairo_lang_utils::ordered_hash_map::{
+    deserialize_ordered_hashmap_vec, serialize_ordered_hashmap_vec, OrderedHashMap,
+};
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
 
 use super::known_stack::KnownStack;
 
 /// Represents the known information about a Sierra variable which contains a deferred value.
 /// For example, `[ap - 1] + [ap - 2]`.
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub struct DeferredVariableInfo {
     /// The type of the variable.
     pub ty: sierra::ids::ConcreteTypeId,
@@ -19,7 +27,7 @@ pub struct DeferredVariableInfo {
 }
 
 /// The type of a deferred variable.
-#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub enum DeferredVariableKind {
     /// See [DeferredOutputKind::Const].
     Const,
@@ -30,7 +38,7 @@ pub enum DeferredVariableKind {
 }
 
 /// Represents the state of Sierra variable.
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub enum VarState {
     /// The variable is a temporary variable with the given type.
     TempVar {
@@ -45,9 +53,13 @@ pub enum VarState {
 
 /// Represents information known about the state of the variables.
 /// For example, which variable contains a deferred value and which variable is on the stack.
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct State {
     /// A map from [sierra::ids::VarId] of to its state.
+    #[serde(
+        serialize_with = "serialize_ordered_hashmap_vec",
+        deserialize_with = "deserialize_ordered_hashmap_vec"
+    )]
     pub variables: OrderedHashMap<sierra::ids::VarId, VarState>,
     /// The information known about the top of the stack.
     pub known_stack: KnownStack,
@@ -152,12 +164,72 @@ impl State {
             self.variables.insert(dst.clone(), var_state.clone());
         }
     }
+
+    /// Checks that [Self::variables] is consistent with [Self::known_stack]:
+    /// * A variable on the known stack is not deferred, as the value of a deferred variable is not
+    ///   stored anywhere.
+    /// * A variable on the known stack is within its depth.
+    ///
+    /// Temporary variables are not required to be on the known stack, as it is cleared on unknown
+    /// ap changes. Variables on the known stack are not required to be in [Self::variables], as the
+    /// variables consumed by libfuncs are kept on it. Local variables may be on the known stack, as
+    /// storing a temporary variable as a local one keeps its copy on the stack.
+    ///
+    /// Returns the variables violating the invariants, if any. Used for debugging.
+    pub fn verify_invariants(&self) -> Result<(), Vec<sierra::ids::VarId>> {
+        let divergent_vars = self
+            .known_stack
+            .snapshot()
+            .into_iter()
+            .filter(|var| {
+                matches!(self.variables.get(var), Some(VarState::Deferred { .. }))
+                    || self.known_stack.get(var).is_some_and(|idx| idx >= 0)
+            })
+            .collect_vec();
+        if divergent_vars.is_empty() {
+            Ok(())
+        } else {
+            Err(divergent_vars)
+        }
+    }
+}
+
+/// A snapshot of the known information about the state of the variables at a given point in the
+/// code.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum StateSnapshot {
+    /// The point in the code is not reachable.
+    Unreachable,
+    /// The point in the code is reachable, with the given information.
+    Reachable {
+        /// A map from [sierra::ids::VarId] to its state.
+        variables: OrderedHashMap<sierra::ids::VarId, VarState>,
+        /// The variables on the known stack, from the top of the stack to its bottom.
+        known_stack: Vec<sierra::ids::VarId>,
+    },
+}
+impl StateSnapshot {
+    /// Creates a snapshot of the given state, where `None` means the point is not reachable.
+    pub fn new(state_opt: Option<&State>) -> Self {
+        match state_opt {
+            None => StateSnapshot::Unreachable,
+            Some(state) => StateSnapshot::Reachable {
+                variables: state.variables.clone(),
+                known_stack: state.known_stack.snapshot(),
+            },
+        }
+    }
 }
 
 /// Merges the information from two [State]s.
 /// Used to determine the state at the merge of two code branches.
 ///
 /// If one of the given states is None, the second is returned.
+///
+/// Otherwise, the variables of the merged state are joined using [merge_var_states], and the known
+/// stacks are merged using [KnownStack::merge_with].
+/// A variable that appears in only one of the states is dropped from the merged state, as it is
+/// not available on all the code paths reaching the merge point.
 pub fn merge_optional_states(a_opt: Option<State>, b_opt: Option<State>) -> Option<State> {
     match (a_opt, b_opt) {
         (None, None) => None,
@@ -168,11 +240,7 @@ pub fn merge_optional_states(a_opt: Option<State>, b_opt: Option<State>) -> Opti
             let mut variables = OrderedHashMap::default();
             for (var, var_state_a) in a.variables {
                 if let Some(var_state_b) = b.variables.get(&var) {
-                    assert_eq!(
-                        var_state_a, *var_state_b,
-                        "Internal compiler error: Found different deferred variables."
-                    );
-                    variables.insert(var, var_state_a);
+                    variables.insert(var, merge_var_states(&var_state_a, var_state_b));
                 }
             }
 
@@ -180,3 +248,25 @@ pub fn merge_optional_states(a_opt: Option<State>, b_opt: Option<State>) -> Opti
         }
     }
 }
+
+/// Joins the states of a single variable that is present in the two merged [State]s.
+///
+/// The two states must be identical, as the state of a variable is determined by the code that
+/// defined it, which is shared by all the branches:
+///
+/// | `a`                  | `b`                                    | Result              |
+/// |----------------------|----------------------------------------|---------------------|
+/// | `TempVar { ty }`     | `TempVar { ty }`                       | `TempVar { ty }`    |
+/// | `TempVar { ty: t1 }` | `TempVar { ty: t2 }` with `t1 != t2`   | Panics.             |
+/// | `Deferred { info }`  | `Deferred { info }`                    | `Deferred { info }` |
+/// | `Deferred` (`k1`)    | `Deferred` (`k2`) with `k1 != k2`      | Panics.             |
+/// | `LocalVar`           | `LocalVar`                             | `LocalVar`          |
+/// | `LocalVar`           | `TempVar` or `Deferred`                | Panics.             |
+/// | `TempVar`            | `Deferred`                             | Panics.             |
+///
+/// The table is symmetric in `a` and `b`. Variables that are present on only one side are not
+/// passed to this function; they are dropped by [merge_optional_states].
+pub fn merge_var_states(a: &VarState, b: &VarState) -> VarState {
+    assert_eq!(a, b, "Internal compiler error: Found different deferred variables.");
+    a.clone()
+}
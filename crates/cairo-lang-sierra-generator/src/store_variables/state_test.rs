@@ -0,0 +1,118 @@
+use cairo_lang_sierra::ids::{ConcreteTypeId, VarId};
+use pretty_assertions::assert_eq;
+use test_log::test;
+
+use super::{merge_optional_states, DeferredVariableInfo, DeferredVariableKind, State, VarState};
+
+fn temp_var(ty: &str) -> VarState {
+    VarState::TempVar { ty: ConcreteTypeId::from_string(ty) }
+}
+
+fn deferred_var(kind: DeferredVariableKind) -> VarState {
+    VarState::Deferred {
+        info: DeferredVariableInfo { ty: ConcreteTypeId::from_string("felt252"), kind },
+    }
+}
+
+/// Creates a [State] with the given variables and an empty known stack.
+fn dummy_state(variables: &[(u64, VarState)]) -> State {
+    State {
+        variables: variables.iter().map(|(var, state)| (VarId::new(*var), state.clone())).collect(),
+        known_stack: Default::default(),
+    }
+}
+
+#[test]
+fn merge_states() {
+    assert!(merge_optional_states(None, None).is_none());
+
+    let a = dummy_state(&[(0, VarState::LocalVar)]);
+    assert_eq!(merge_optional_states(Some(a.clone()), None).unwrap().variables, a.variables);
+    assert_eq!(merge_optional_states(None, Some(a.clone())).unwrap().variables, a.variables);
+
+    // Variables that are present on one side only are dropped.
+    let a = dummy_state(&[
+        (0, VarState::LocalVar),
+        (1, temp_var("felt252")),
+        (2, deferred_var(DeferredVariableKind::AddConst)),
+        (3, VarState::LocalVar),
+    ]);
+    let b = dummy_state(&[
+        (4, VarState::LocalVar),
+        (2, deferred_var(DeferredVariableKind::AddConst)),
+        (1, temp_var("felt252")),
+        (0, VarState::LocalVar),
+    ]);
+    let res = dummy_state(&[
+        (0, VarState::LocalVar),
+        (1, temp_var("felt252")),
+        (2, deferred_var(DeferredVariableKind::AddConst)),
+    ]);
+    assert_eq!(merge_optional_states(Some(a), Some(b)).unwrap().variables, res.variables);
+}
+
+#[test]
+#[should_panic(expected = "Found different deferred variables.")]
+fn merge_states_different_temp_types() {
+    let a = dummy_state(&[(0, temp_var("felt252"))]);
+    let b = dummy_state(&[(0, temp_var("u128"))]);
+    merge_optional_states(Some(a), Some(b));
+}
+
+#[test]
+#[should_panic(expected = "Found different deferred variables.")]
+fn merge_states_different_deferred_kinds() {
+    let a = dummy_state(&[(0, deferred_var(DeferredVariableKind::Generic))]);
+    let b = dummy_state(&[(0, deferred_var(DeferredVariableKind::Const))]);
+    merge_optional_states(Some(a), Some(b));
+}
+
+#[test]
+#[should_panic(expected = "Found different deferred variables.")]
+fn merge_states_local_and_temp() {
+    let a = dummy_state(&[(0, VarState::LocalVar)]);
+    let b = dummy_state(&[(0, temp_var("felt252"))]);
+    merge_optional_states(Some(a), Some(b));
+}
+
+#[test]
+fn verify_state_invariants() {
+    let mut state = dummy_state(&[
+        (0, temp_var("felt252")),
+        (1, temp_var("felt252")),
+        (2, deferred_var(DeferredVariableKind::Generic)),
+    ]);
+    // A temporary variable that is not on the known stack is consistent.
+    state.known_stack.insert(VarId::new(0), 0);
+    state.known_stack.update_offset_by_max();
+    assert_eq!(state.verify_invariants(), Ok(()));
+
+    state.known_stack.insert(VarId::new(2), 1);
+    state.known_stack.update_offset_by_max();
+    assert_eq!(state.verify_invariants(), Err(vec![VarId::new(2)]));
+    state.known_stack.remove_variable(&VarId::new(2));
+    assert_eq!(state.verify_invariants(), Ok(()));
+
+    // A variable beyond the depth of the known stack.
+    state.known_stack.insert(VarId::new(1), 0);
+    assert_eq!(state.verify_invariants(), Err(vec![VarId::new(1)]));
+}
+
+/// Tests that a [State] is unchanged by serializing and deserializing it.
+#[test]
+fn state_serde_round_trip() {
+    let mut state = dummy_state(&[
+        (0, VarState::LocalVar),
+        (1, temp_var("felt252")),
+        (2, deferred_var(DeferredVariableKind::Const)),
+        (3, deferred_var(DeferredVariableKind::Generic)),
+    ]);
+    state.known_stack.insert(VarId::new(1), 0);
+    state.known_stack.update_offset_by_max();
+
+    let serialized = serde_json::to_string(&state).unwrap();
+    let deserialized: State = serde_json::from_str(&serialized).unwrap();
+    assert_eq!(deserialized.variables, state.variables);
+    assert_eq!(deserialized.known_stack, state.known_stack);
+    assert_eq!(serde_json::to_string(&deserialized).unwrap(), serialized);
+}
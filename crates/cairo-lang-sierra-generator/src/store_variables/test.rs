@@ -1,24 +1,39 @@
+use std::cell::Cell;
+
 use cairo_lang_semantic::corelib::get_core_ty_by_name;
+use cairo_lang_semantic::db::SemanticGroup;
 use cairo_lang_semantic::GenericArgumentId;
 use cairo_lang_sierra::extensions::lib_func::{
     BranchSignature, DeferredOutputKind, LibfuncSignature, OutputVarInfo, ParamSignature,
     SierraApChange,
 };
 use cairo_lang_sierra::extensions::OutputVarReferenceInfo;
-use cairo_lang_sierra::ids::ConcreteLibfuncId;
+use cairo_lang_sierra::ids::{ConcreteLibfuncId, ConcreteTypeId, VarId};
+use cairo_lang_sierra::program::{GenStatement, Param};
 use cairo_lang_utils::ordered_hash_map::OrderedHashMap;
+use cairo_lang_utils::ordered_hash_set::OrderedHashSet;
+use cairo_lang_utils::Upcast;
+use itertools::Itertools;
 use pretty_assertions::assert_eq;
 
+use super::state::{DeferredVariableInfo, DeferredVariableKind, StateSnapshot, VarState};
 use super::{LibfuncInfo, LocalVariables};
 use crate::db::SierraGenGroup;
 use crate::pre_sierra;
 use crate::replace_ids::replace_sierra_ids;
-use crate::store_variables::add_store_statements;
+use crate::store_variables::{
+    add_store_statements, add_store_statements_cached, add_store_statements_debug,
+    add_store_statements_full, add_store_statements_with_config, add_store_statements_with_stats,
+    requires_stores, AddStoreVariableStatements, BranchStoreInfo, Disposition, MissingVarPolicy,
+    ScratchRegion, StoreError, StoreLibfuncProvider, StoreOutput, StorePass, StorePolicy,
+    StoreVariablesConfig,
+};
 use crate::test_utils::{
     as_var_id_vec, dummy_jump_statement, dummy_label, dummy_push_values, dummy_push_values_ex,
-    dummy_return_statement, dummy_simple_branch, dummy_simple_statement,
+    dummy_return_statement, dummy_simple_branch, dummy_simple_statement, label_id_from_usize,
     SierraGenDatabaseForTesting,
 };
+use crate::utils::{simple_statement, store_temp_libfunc_id};
 
 /// Returns the [OutputVarReferenceInfo] information for a given libfunc.
 /// All libfuncs inputs and outputs are felt252s, since [dummy_push_values] is currently with
@@ -93,14 +108,7 @@ fn get_lib_func_signature(db: &dyn SierraGenGroup, libfunc: ConcreteLibfuncId) -
             }],
             fallthrough: Some(0),
         },
-        "nope" => LibfuncSignature {
-            param_signatures: vec![],
-            branch_signatures: vec![BranchSignature {
-                vars: vec![],
-                ap_change: SierraApChange::Known { new_vars_only: true },
-            }],
-            fallthrough: Some(0),
-        },
+        "nope" => mock_simple_libfunc_info(&[], &[]).signature,
         "revoke_ap" => LibfuncSignature {
             param_signatures: vec![],
             branch_signatures: vec![BranchSignature {
@@ -133,35 +141,11 @@ fn get_lib_func_signature(db: &dyn SierraGenGroup, libfunc: ConcreteLibfuncId) -
             }],
             fallthrough: None,
         },
-        "branch" => LibfuncSignature {
-            param_signatures: vec![],
-            branch_signatures: vec![
-                BranchSignature {
-                    vars: vec![],
-                    ap_change: SierraApChange::Known { new_vars_only: true },
-                },
-                BranchSignature {
-                    vars: vec![],
-                    ap_change: SierraApChange::Known { new_vars_only: true },
-                },
-            ],
-            fallthrough: Some(1),
-        },
-        "branch_with_param" => LibfuncSignature {
-            param_signatures: vec![ParamSignature::new(felt252_ty)],
-            branch_signatures: vec![
-                BranchSignature {
-                    vars: vec![],
-                    ap_change: SierraApChange::Known { new_vars_only: true },
-                },
-                BranchSignature {
-                    vars: vec![],
-                    ap_change: SierraApChange::Known { new_vars_only: true },
-                },
-            ],
-            fallthrough: Some(1),
-        },
-        "store_temp<felt252>" => LibfuncSignature {
+        "branch" => mock_two_branch_libfunc_info(&[]).signature,
+        "branch_with_param" => {
+            mock_two_branch_libfunc_info(&[ParamSignature::new(felt252_ty)]).signature
+        }
+        "store_temp" | "store_temp<felt252>" => LibfuncSignature {
             param_signatures: vec![ParamSignature {
                 ty: felt252_ty.clone(),
                 allow_deferred: true,
@@ -177,6 +161,11 @@ fn get_lib_func_signature(db: &dyn SierraGenGroup, libfunc: ConcreteLibfuncId) -
             }],
             fallthrough: Some(0),
         },
+        "alloc_local" => LibfuncSignature::new_non_branch(
+            vec![],
+            vec![OutputVarInfo { ty: felt252_ty, ref_info: OutputVarReferenceInfo::SimpleDerefs }],
+            SierraApChange::Known { new_vars_only: true },
+        ),
         "temp_not_on_top" => LibfuncSignature::new_non_branch(
             vec![],
             vec![OutputVarInfo {
@@ -209,148 +198,1703 @@ fn get_lib_func_signature(db: &dyn SierraGenGroup, libfunc: ConcreteLibfuncId) -
     }
 }
 
-/// Helper function for tests of [add_store_statements].
-///
-/// Calls [add_store_statements] on the given `statements` and returns the result as a vector of
-/// strings.
-fn test_add_store_statements(
-    db: &SierraGenDatabaseForTesting,
-    statements: Vec<pre_sierra::Statement>,
-    local_variables: LocalVariables,
-    params: &[&str],
-) -> Vec<String> {
-    add_store_statements(
-        db,
-        statements,
-        &(|libfunc| LibfuncInfo { signature: get_lib_func_signature(db, libfunc) }),
-        local_variables,
-        &as_var_id_vec(params),
-    )
-    .iter()
-    .map(|statement| replace_sierra_ids(db, statement).to_string(db))
-    .collect()
+/// Returns a [LibfuncInfo] with the given parameters and branches, where the last branch is the
+/// fallthrough branch.
+fn mock_libfunc_info(params: &[ParamSignature], branches: &[BranchSignature]) -> LibfuncInfo {
+    LibfuncInfo {
+        signature: LibfuncSignature {
+            param_signatures: params.to_vec(),
+            branch_signatures: branches.to_vec(),
+            fallthrough: branches.len().checked_sub(1),
+        },
+    }
+}
+
+/// Returns a [LibfuncInfo] with the given parameters and a single fallthrough branch with the given
+/// outputs, where only the outputs are pushed onto the stack.
+fn mock_simple_libfunc_info(params: &[ParamSignature], vars: &[OutputVarInfo]) -> LibfuncInfo {
+    mock_libfunc_info(
+        params,
+        &[BranchSignature {
+            vars: vars.to_vec(),
+            ap_change: SierraApChange::Known { new_vars_only: true },
+        }],
+    )
+}
+
+/// Returns a [LibfuncInfo] with the given parameters and two branches without outputs, where the
+/// second branch is the fallthrough branch.
+fn mock_two_branch_libfunc_info(params: &[ParamSignature]) -> LibfuncInfo {
+    let branch =
+        BranchSignature { vars: vec![], ap_change: SierraApChange::Known { new_vars_only: true } };
+    mock_libfunc_info(params, &[branch.clone(), branch])
+}
+
+/// Helper function for tests of [add_store_statements].
+///
+/// Calls [add_store_statements] on the given `statements` and returns the result as a vector of
+/// strings.
+fn test_add_store_statements(
+    db: &SierraGenDatabaseForTesting,
+    statements: Vec<pre_sierra::Statement>,
+    local_variables: LocalVariables,
+    params: &[&str],
+) -> Vec<String> {
+    add_store_statements(
+        db,
+        statements,
+        &(|libfunc| LibfuncInfo { signature: get_lib_func_signature(db, libfunc) }),
+        local_variables,
+        &as_var_id_vec(params),
+    )
+    .iter()
+    .map(|statement| replace_sierra_ids(db, statement).to_string(db))
+    .collect()
+}
+
+/// Returns the given variables as parameters of type felt252, the type of all the variables of the
+/// tests.
+fn felt252_params(db: &SierraGenDatabaseForTesting, params: &[&str]) -> Vec<Param> {
+    let felt252_ty =
+        db.get_concrete_type_id(db.core_felt252_ty()).expect("Can't find core::felt252.");
+    as_var_id_vec(params).into_iter().map(|id| Param { id, ty: felt252_ty.clone() }).collect()
+}
+
+#[test]
+fn store_temp_simple() {
+    let db = SierraGenDatabaseForTesting::default();
+    let statements: Vec<pre_sierra::Statement> = vec![
+        dummy_simple_statement(&db, "felt252_add", &["0", "1"], &["2"]),
+        dummy_simple_statement(&db, "nope", &[], &[]),
+        dummy_simple_statement(&db, "felt252_add", &["2", "3"], &["4"]),
+        dummy_simple_statement(&db, "nope", &[], &[]),
+        dummy_simple_statement(&db, "felt252_add", &["5", "4"], &["5"]),
+        dummy_simple_statement(&db, "nope", &[], &[]),
+        dummy_label(&db, 0),
+        dummy_simple_statement(&db, "felt252_add", &["5", "6"], &["6"]),
+        dummy_return_statement(&[]),
+    ];
+
+    assert_eq!(
+        test_add_store_statements(
+            &db,
+            statements,
+            LocalVariables::default(),
+            &["0", "1", "3", "5", "6"]
+        ),
+        vec![
+            "felt252_add(0, 1) -> (2)",
+            "nope() -> ()",
+            "store_temp<felt252>(2) -> (2)",
+            "felt252_add(2, 3) -> (4)",
+            "nope() -> ()",
+            "store_temp<felt252>(4) -> (4)",
+            "felt252_add(5, 4) -> (5)",
+            "nope() -> ()",
+            "label_test::test::0:",
+            "store_temp<felt252>(5) -> (5)",
+            "felt252_add(5, 6) -> (6)",
+            "return()",
+        ]
+    );
+}
+
+#[test]
+fn store_temp_for_branch_command() {
+    let db = SierraGenDatabaseForTesting::default();
+    let statements: Vec<pre_sierra::Statement> = vec![
+        dummy_simple_statement(&db, "felt252_add", &["0", "1"], &["2"]),
+        dummy_simple_branch(&db, "branch_with_param", &["2"], 0),
+        dummy_label(&db, 0),
+        dummy_return_statement(&[]),
+    ];
+
+    assert_eq!(
+        test_add_store_statements(&db, statements, LocalVariables::default(), &["0", "1"]),
+        vec![
+            "felt252_add(0, 1) -> (2)",
+            "store_temp<felt252>(2) -> (2)",
+            "branch_with_param(2) { label_test::test::0() fallthrough() }",
+            "label_test::test::0:",
+            "return()",
+        ]
+    );
+}
+
+#[test]
+fn store_local_simple() {
+    let db = SierraGenDatabaseForTesting::default();
+    let statements: Vec<pre_sierra::Statement> = vec![
+        dummy_simple_statement(&db, "felt252_add", &["0", "1"], &["2"]),
+        dummy_simple_statement(&db, "nope", &[], &[]),
+        // Case I: local added instead of tempvar, when first used.
+        dummy_simple_statement(&db, "felt252_add", &["2", "3"], &["4"]),
+        dummy_simple_statement(&db, "nope", &[], &[]),
+        // Case II: deferred computed into local before revoke_ap().
+        dummy_simple_statement(&db, "revoke_ap", &[], &[]),
+        dummy_simple_statement(&db, "function_call4", &[], &["5", "6", "7", "8"]),
+        dummy_simple_statement(&db, "nope", &[], &[]),
+        // Case III: tempvar copied into local before revoke_ap().
+        dummy_simple_statement(&db, "revoke_ap", &[], &[]),
+        dummy_simple_statement(&db, "store_temp<felt252>", &["9"], &["9"]),
+        // Don't store as local due to a simple jump.
+        dummy_jump_statement(&db, 0),
+        dummy_label(&db, 0),
+        dummy_simple_statement(&db, "nope", &[], &[]),
+        // Case IV: tempvar copied into local before branches.
+        dummy_simple_branch(&db, "branch", &[], 1),
+        dummy_label(&db, 1),
+        dummy_return_statement(&[]),
+    ];
+
+    assert_eq!(
+        test_add_store_statements(
+            &db,
+            statements,
+            OrderedHashMap::from_iter(vec![
+                ("2".into(), "102".into()),
+                ("4".into(), "104".into()),
+                ("7".into(), "107".into()),
+                ("9".into(), "109".into())
+            ]),
+            &["0", "1", "3", "9"]
+        ),
+        vec![
+            "felt252_add(0, 1) -> (2)",
+            "nope() -> ()",
+            "store_local<felt252>(102, 2) -> (2)",
+            "felt252_add(2, 3) -> (4)",
+            "nope() -> ()",
+            "store_local<felt252>(104, 4) -> (4)",
+            "revoke_ap() -> ()",
+            "function_call4() -> (5, 6, 7, 8)",
+            "nope() -> ()",
+            "store_local<felt252>(107, 7) -> (7)",
+            "revoke_ap() -> ()",
+            "store_temp<felt252>(9) -> (9)",
+            "jump() { label_test::test::0() }",
+            "label_test::test::0:",
+            "nope() -> ()",
+            "store_local<felt252>(109, 9) -> (9)",
+            "branch() { label_test::test::1() fallthrough() }",
+            "label_test::test::1:",
+            "return()",
+        ],
+    );
+}
+
+/// Tests that the dispositions of stored variables and of variables consumed while deferred are
+/// reported.
+#[test]
+fn store_dispositions() {
+    let db = SierraGenDatabaseForTesting::default();
+    let statements: Vec<pre_sierra::Statement> = vec![
+        dummy_simple_statement(&db, "felt252_const", &[], &["0"]),
+        dummy_simple_statement(&db, "felt252_add", &["1", "0"], &["2"]),
+        dummy_simple_statement(&db, "felt252_add", &["2", "3"], &["4"]),
+        dummy_push_values(&db, &[("4", "100")]),
+        dummy_return_statement(&["100"]),
+    ];
+
+    let StoreOutput { statements, dispositions, .. } = add_store_statements_with_config(
+        &db,
+        statements,
+        &(|libfunc| LibfuncInfo { signature: get_lib_func_signature(&db, libfunc) }),
+        OrderedHashMap::from_iter(vec![("2".into(), "102".into())]),
+        &felt252_params(&db, &["1", "3"]),
+        StoreVariablesConfig { collect_dispositions: true, ..Default::default() },
+    )
+    .unwrap();
+    assert_eq!(
+        statements
+            .iter()
+            .map(|statement| replace_sierra_ids(&db, statement).to_string(&db))
+            .collect_vec(),
+        vec![
+            "felt252_const() -> (0)",
+            "felt252_add(1, 0) -> (2)",
+            "store_local<felt252>(102, 2) -> (2)",
+            "felt252_add(2, 3) -> (4)",
+            "store_temp<felt252>(4) -> (100)",
+            "return(100)",
+        ]
+    );
+    assert_eq!(
+        dispositions,
+        OrderedHashMap::from_iter([
+            ("0".into(), Disposition::ConsumedDeferred),
+            ("2".into(), Disposition::StoredLocal),
+            ("4".into(), Disposition::StoredTemp),
+        ])
+    );
+}
+
+/// Tests that the signature of a libfunc invoked many times is fetched once.
+#[test]
+fn store_variables_cached_signatures() {
+    let db = SierraGenDatabaseForTesting::default();
+    let statements = || {
+        (0..1000)
+            .map(|_| dummy_simple_statement(&db, "nope", &[], &[]))
+            .chain([dummy_return_statement(&[])])
+            .collect_vec()
+    };
+    let fetch_count = Cell::new(0);
+    let get_lib_func_info = |libfunc| {
+        fetch_count.set(fetch_count.get() + 1);
+        LibfuncInfo { signature: get_lib_func_signature(&db, libfunc) }
+    };
+
+    let cached = add_store_statements_with_config(
+        &db,
+        statements(),
+        &get_lib_func_info,
+        LocalVariables::default(),
+        &[],
+        StoreVariablesConfig { cache_signatures: true, ..Default::default() },
+    )
+    .unwrap()
+    .statements;
+    assert_eq!(fetch_count.get(), 1);
+    fetch_count.set(0);
+    let cached_shorthand = add_store_statements_cached(
+        &db,
+        statements(),
+        &get_lib_func_info,
+        LocalVariables::default(),
+        &[],
+    );
+    assert_eq!(fetch_count.get(), 1);
+    assert_eq!(cached_shorthand, cached);
+    fetch_count.set(0);
+    let uncached =
+        add_store_statements(&db, statements(), &get_lib_func_info, LocalVariables::default(), &[]);
+    assert_eq!(fetch_count.get(), 1000);
+    assert_eq!(cached, uncached);
+}
+
+#[test]
+fn store_local_with_scratch_region() {
+    let db = SierraGenDatabaseForTesting::default();
+    let statements: Vec<pre_sierra::Statement> = vec![
+        dummy_simple_statement(&db, "felt252_add", &["0", "1"], &["2"]),
+        dummy_simple_statement(&db, "nope", &[], &[]),
+        dummy_simple_statement(&db, "felt252_add", &["2", "3"], &["4"]),
+        dummy_simple_statement(&db, "nope", &[], &[]),
+        dummy_simple_statement(&db, "revoke_ap", &[], &[]),
+        dummy_return_statement(&[]),
+    ];
+    let felt252_ty =
+        db.get_concrete_type_id(db.core_felt252_ty()).expect("Can't find core::felt252.");
+    // The region has a single variable, so only the first local variable is stored into it.
+    let scratch_region =
+        ScratchRegion { slots: OrderedHashMap::from_iter([(felt252_ty, vec!["200".into()])]) };
+
+    assert_eq!(
+        add_store_statements_with_config(
+            &db,
+            statements,
+            &(|libfunc| LibfuncInfo { signature: get_lib_func_signature(&db, libfunc) }),
+            OrderedHashMap::from_iter(vec![("2".into(), "102".into()), ("4".into(), "104".into())]),
+            &felt252_params(&db, &["0", "1", "3"]),
+            StoreVariablesConfig { scratch_region: Some(&scratch_region), ..Default::default() }
+        )
+        .unwrap()
+        .statements
+        .iter()
+        .map(|statement| replace_sierra_ids(&db, statement).to_string(&db))
+        .collect_vec(),
+        vec![
+            "felt252_add(0, 1) -> (2)",
+            "nope() -> ()",
+            "store_local<felt252>(200, 2) -> (2)",
+            "felt252_add(2, 3) -> (4)",
+            "nope() -> ()",
+            "store_local<felt252>(104, 4) -> (4)",
+            "revoke_ap() -> ()",
+            "return()",
+        ]
+    );
+}
+
+/// Tests that a variable preferred as a local variable is stored into the scratch region, while
+/// the other variables are stored as temporary variables.
+#[test]
+fn store_local_by_preference() {
+    let db = SierraGenDatabaseForTesting::default();
+    let statements: Vec<pre_sierra::Statement> = vec![
+        dummy_simple_statement(&db, "felt252_add", &["0", "1"], &["2"]),
+        dummy_simple_statement(&db, "nope", &[], &[]),
+        dummy_simple_statement(&db, "felt252_add", &["2", "3"], &["4"]),
+        dummy_simple_statement(&db, "nope", &[], &[]),
+        dummy_simple_statement(&db, "felt252_add", &["4", "6"], &["5"]),
+        dummy_return_statement(&[]),
+    ];
+    let felt252_ty =
+        db.get_concrete_type_id(db.core_felt252_ty()).expect("Can't find core::felt252.");
+    let scratch_region =
+        ScratchRegion { slots: OrderedHashMap::from_iter([(felt252_ty, vec!["200".into()])]) };
+    let mut prefer_local = |var: &VarId, _ty: &ConcreteTypeId| *var == VarId::from("4");
+
+    assert_eq!(
+        add_store_statements_with_config(
+            &db,
+            statements,
+            &(|libfunc| LibfuncInfo { signature: get_lib_func_signature(&db, libfunc) }),
+            LocalVariables::default(),
+            &felt252_params(&db, &["0", "1", "3", "6"]),
+            StoreVariablesConfig {
+                scratch_region: Some(&scratch_region),
+                prefer_local: Some(&mut prefer_local),
+                ..Default::default()
+            }
+        )
+        .unwrap()
+        .statements
+        .iter()
+        .map(|statement| replace_sierra_ids(&db, statement).to_string(&db))
+        .collect_vec(),
+        vec![
+            "felt252_add(0, 1) -> (2)",
+            "nope() -> ()",
+            "store_temp<felt252>(2) -> (2)",
+            "felt252_add(2, 3) -> (4)",
+            "nope() -> ()",
+            "store_local<felt252>(200, 4) -> (4)",
+            "felt252_add(4, 6) -> (5)",
+            "return()",
+        ]
+    );
+}
+
+/// A [StorePass] keeping only the first statements.
+struct TruncatePass(usize);
+impl StorePass for TruncatePass {
+    fn run(&self, mut statements: Vec<pre_sierra::Statement>) -> Vec<pre_sierra::Statement> {
+        statements.truncate(self.0);
+        statements
+    }
+}
+
+/// A [StorePass] appending a statement.
+struct AppendPass(pre_sierra::Statement);
+impl StorePass for AppendPass {
+    fn run(&self, mut statements: Vec<pre_sierra::Statement>) -> Vec<pre_sierra::Statement> {
+        statements.push(self.0.clone());
+        statements
+    }
+}
+
+/// Tests that the passes run on the statements after the stores are added, in the given order.
+#[test]
+fn store_passes() {
+    let db = SierraGenDatabaseForTesting::default();
+    let statements = || -> Vec<pre_sierra::Statement> {
+        vec![
+            dummy_simple_statement(&db, "felt252_add", &["0", "1"], &["2"]),
+            dummy_simple_statement(&db, "nope", &[], &[]),
+            dummy_simple_statement(&db, "felt252_add", &["2", "3"], &["4"]),
+            dummy_return_statement(&[]),
+        ]
+    };
+    let truncate = TruncatePass(4);
+    let append = AppendPass(dummy_return_statement(&["4"]));
+    let run_passes = |passes: &[&dyn StorePass]| {
+        add_store_statements_with_config(
+            &db,
+            statements(),
+            &(|libfunc| LibfuncInfo { signature: get_lib_func_signature(&db, libfunc) }),
+            LocalVariables::default(),
+            &felt252_params(&db, &["0", "1", "3"]),
+            StoreVariablesConfig { passes, ..Default::default() },
+        )
+        .unwrap()
+        .statements
+        .iter()
+        .map(|statement| replace_sierra_ids(&db, statement).to_string(&db))
+        .collect_vec()
+    };
+
+    assert_eq!(
+        run_passes(&[&truncate, &append]),
+        vec![
+            "felt252_add(0, 1) -> (2)",
+            "nope() -> ()",
+            "store_temp<felt252>(2) -> (2)",
+            "felt252_add(2, 3) -> (4)",
+            "return(4)",
+        ]
+    );
+    assert_eq!(
+        run_passes(&[&append, &truncate]),
+        vec![
+            "felt252_add(0, 1) -> (2)",
+            "nope() -> ()",
+            "store_temp<felt252>(2) -> (2)",
+            "felt252_add(2, 3) -> (4)",
+        ]
+    );
+}
+
+/// Tests handling a function in two segments, where the state at a label branched to by the first
+/// segment is passed to the second segment.
+#[test]
+fn store_variables_with_future_states() {
+    let db = SierraGenDatabaseForTesting::default();
+    let get_lib_func_info =
+        |libfunc| LibfuncInfo { signature: get_lib_func_signature(&db, libfunc) };
+    let first_segment: Vec<pre_sierra::Statement> = vec![
+        dummy_simple_statement(&db, "felt252_add", &["0", "1"], &["2"]),
+        dummy_simple_branch(&db, "branch", &[], 0),
+        dummy_return_statement(&[]),
+    ];
+    let second_segment: Vec<pre_sierra::Statement> = vec![
+        dummy_label(&db, 0),
+        dummy_simple_statement(&db, "felt252_add3", &["2"], &["3"]),
+        dummy_return_statement(&[]),
+    ];
+
+    let StoreOutput { statements: first_result, future_states, .. } =
+        add_store_statements_with_config(
+            &db,
+            first_segment,
+            &get_lib_func_info,
+            LocalVariables::default(),
+            &felt252_params(&db, &["0", "1"]),
+            StoreVariablesConfig {
+                future_states: Some(OrderedHashMap::default()),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    assert_eq!(
+        first_result
+            .iter()
+            .map(|statement| replace_sierra_ids(&db, statement).to_string(&db))
+            .collect_vec(),
+        vec![
+            "felt252_add(0, 1) -> (2)",
+            "store_temp<felt252>(2) -> (2)",
+            "branch() { label_test::test::0() fallthrough() }",
+            "return()",
+        ]
+    );
+    let felt252_ty =
+        db.get_concrete_type_id(db.core_felt252_ty()).expect("Can't find core::felt252.");
+    assert_eq!(future_states.keys().copied().collect_vec(), vec![label_id_from_usize(&db, 0)]);
+    assert_eq!(
+        future_states[label_id_from_usize(&db, 0)].variables.get(&VarId::from("2")),
+        Some(&VarState::TempVar { ty: felt252_ty })
+    );
+
+    // The second segment is only reached by branching to its label, where `2` is already stored.
+    let StoreOutput { statements: second_result, future_states, .. } =
+        add_store_statements_with_config(
+            &db,
+            second_segment,
+            &get_lib_func_info,
+            LocalVariables::default(),
+            &[],
+            StoreVariablesConfig {
+                starts_unreachable: true,
+                future_states: Some(future_states),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    assert_eq!(
+        second_result
+            .iter()
+            .map(|statement| replace_sierra_ids(&db, statement).to_string(&db))
+            .collect_vec(),
+        vec!["label_test::test::0:", "felt252_add3(2) -> (3)", "return()"]
+    );
+    assert!(future_states.is_empty());
+}
+
+#[test]
+fn store_comments() {
+    let db = SierraGenDatabaseForTesting::default();
+    let statements: Vec<pre_sierra::Statement> = vec![
+        dummy_simple_statement(&db, "felt252_add", &["0", "1"], &["2"]),
+        dummy_simple_statement(&db, "felt252_add", &["2", "3"], &["4"]),
+        dummy_simple_statement(&db, "revoke_ap", &[], &[]),
+        dummy_push_values(&db, &[("4", "100")]),
+        dummy_return_statement(&["100"]),
+    ];
+
+    let StoreOutput { statements, comments, .. } = add_store_statements_with_config(
+        &db,
+        statements,
+        &(|libfunc| LibfuncInfo { signature: get_lib_func_signature(&db, libfunc) }),
+        OrderedHashMap::from_iter(vec![("4".into(), "104".into())]),
+        &felt252_params(&db, &["0", "1", "3"]),
+        StoreVariablesConfig { collect_comments: true, ..Default::default() },
+    )
+    .unwrap();
+    let dump: Vec<String> = statements
+        .iter()
+        .enumerate()
+        .map(|(idx, statement)| {
+            let statement = replace_sierra_ids(&db, statement).to_string(&db);
+            match comments.get(&idx) {
+                Some(comment) => format!("{statement} // {comment}"),
+                None => statement,
+            }
+        })
+        .collect();
+    assert_eq!(
+        dump,
+        vec![
+            "felt252_add(0, 1) -> (2)",
+            "store_temp<felt252>(2) -> (2) // deferred argument not allowed by libfunc",
+            "felt252_add(2, 3) -> (4)",
+            "store_local<felt252>(104, 4) -> (4) // local variable before an unknown ap change",
+            "revoke_ap() -> ()",
+            "store_temp<felt252>(4) -> (100) // pushing values onto the stack",
+            "return(100)",
+        ]
+    );
+}
+
+/// Tests that a `store_temp` repeating the preceding one is skipped only if
+/// [StoreVariablesConfig::skip_redundant_store_temp] is set.
+#[test]
+fn redundant_store_temp() {
+    let db = SierraGenDatabaseForTesting::default();
+    let felt252_ty =
+        db.get_concrete_type_id(db.core_felt252_ty()).expect("Can't find core::felt252.");
+    let store_temp = || {
+        simple_statement(
+            store_temp_libfunc_id(&db, felt252_ty.clone()),
+            &["2".into()],
+            &["2".into()],
+        )
+    };
+
+    let run = |statements, skip_redundant_store_temp| {
+        add_store_statements_with_config(
+            &db,
+            statements,
+            &(|libfunc| LibfuncInfo { signature: get_lib_func_signature(&db, libfunc) }),
+            LocalVariables::default(),
+            &felt252_params(&db, &["0", "1"]),
+            StoreVariablesConfig { skip_redundant_store_temp, ..Default::default() },
+        )
+        .unwrap()
+        .statements
+        .iter()
+        .map(|statement| replace_sierra_ids(&db, statement).to_string(&db))
+        .collect_vec()
+    };
+
+    // Consecutive stores of the same variable are collapsed.
+    let statements = || -> Vec<pre_sierra::Statement> {
+        vec![
+            dummy_simple_statement(&db, "felt252_add", &["0", "1"], &["2"]),
+            store_temp(),
+            store_temp(),
+            dummy_return_statement(&["2"]),
+        ]
+    };
+    assert_eq!(
+        run(statements(), true),
+        vec!["felt252_add(0, 1) -> (2)", "store_temp<felt252>(2) -> (2)", "return(2)"]
+    );
+    // By default, the given statements are kept as is.
+    assert_eq!(
+        run(statements(), false),
+        vec![
+            "felt252_add(0, 1) -> (2)",
+            "store_temp<felt252>(2) -> (2)",
+            "store_temp<felt252>(2) -> (2)",
+            "return(2)",
+        ]
+    );
+
+    // Stores separated by a label are kept.
+    let statements: Vec<pre_sierra::Statement> = vec![
+        dummy_simple_statement(&db, "felt252_add", &["0", "1"], &["2"]),
+        store_temp(),
+        dummy_label(&db, 0),
+        store_temp(),
+        dummy_return_statement(&["2"]),
+    ];
+    assert_eq!(
+        run(statements, true),
+        vec![
+            "felt252_add(0, 1) -> (2)",
+            "store_temp<felt252>(2) -> (2)",
+            "label_test::test::0:",
+            "store_temp<felt252>(2) -> (2)",
+            "return(2)",
+        ]
+    );
+}
+
+/// A [StoreLibfuncProvider] returning mock libfunc ids.
+struct MockStoreLibfuncProvider;
+impl StoreLibfuncProvider for MockStoreLibfuncProvider {
+    fn store_temp_libfunc_id(
+        &self,
+        _db: &dyn SierraGenGroup,
+        _ty: ConcreteTypeId,
+    ) -> ConcreteLibfuncId {
+        "mock_store_temp".into()
+    }
+
+    fn store_local_libfunc_id(
+        &self,
+        _db: &dyn SierraGenGroup,
+        _ty: ConcreteTypeId,
+    ) -> ConcreteLibfuncId {
+        "mock_store_local".into()
+    }
+}
+
+#[test]
+fn libfunc_provider() {
+    let db = SierraGenDatabaseForTesting::default();
+    let statements: Vec<pre_sierra::Statement> = vec![
+        dummy_simple_statement(&db, "felt252_add", &["0", "1"], &["2"]),
+        dummy_simple_statement(&db, "felt252_add", &["2", "3"], &["4"]),
+        dummy_simple_statement(&db, "revoke_ap", &[], &[]),
+        dummy_return_statement(&[]),
+    ];
+
+    let statements = add_store_statements_with_config(
+        &db,
+        statements,
+        &(|libfunc| LibfuncInfo { signature: get_lib_func_signature(&db, libfunc) }),
+        OrderedHashMap::from_iter(vec![("4".into(), "104".into())]),
+        &felt252_params(&db, &["0", "1", "3"]),
+        StoreVariablesConfig { libfunc_provider: &MockStoreLibfuncProvider, ..Default::default() },
+    )
+    .unwrap()
+    .statements;
+    // Only the ids returned by the provider have a debug name.
+    assert_eq!(
+        statements
+            .iter()
+            .map(|statement| match statement {
+                pre_sierra::Statement::Sierra(GenStatement::Invocation(invocation)) => {
+                    invocation.libfunc_id.debug_name.as_ref().map(|name| name.to_string())
+                }
+                _ => None,
+            })
+            .collect_vec(),
+        vec![
+            None,
+            Some("mock_store_temp".to_string()),
+            None,
+            Some("mock_store_local".to_string()),
+            None,
+            None,
+        ]
+    );
+}
+
+#[test]
+fn store_stats() {
+    let db = SierraGenDatabaseForTesting::default();
+    let input_statements: Vec<pre_sierra::Statement> = vec![
+        dummy_simple_statement(&db, "felt252_add", &["0", "1"], &["2"]),
+        dummy_simple_statement(&db, "felt252_add", &["2", "3"], &["4"]),
+        dummy_simple_statement(&db, "felt252_add", &["4", "8"], &["5"]),
+        // Stored since it is a local variable, which is not counted.
+        dummy_simple_statement(&db, "felt252_add", &["5", "9"], &["6"]),
+        dummy_simple_statement(&db, "felt252_add3", &["6"], &["7"]),
+        dummy_return_statement(&[]),
+    ];
+
+    let get_libfunc_info =
+        |libfunc| LibfuncInfo { signature: get_lib_func_signature(&db, libfunc) };
+    let local_variables: LocalVariables =
+        OrderedHashMap::from_iter(vec![("5".into(), "105".into())]);
+    let StoreOutput { statements, stats, .. } = add_store_statements_with_config(
+        &db,
+        input_statements.clone(),
+        &get_libfunc_info,
+        local_variables.clone(),
+        &felt252_params(&db, &["0", "1", "3", "8", "9"]),
+        StoreVariablesConfig { collect_stats: true, ..Default::default() },
+    )
+    .unwrap();
+    assert_eq!(
+        add_store_statements_with_stats(
+            &db,
+            input_statements,
+            &get_libfunc_info,
+            local_variables,
+            &as_var_id_vec(&["0", "1", "3", "8", "9"]),
+        ),
+        (statements.clone(), stats.clone())
+    );
+    assert_eq!(
+        statements
+            .iter()
+            .map(|statement| replace_sierra_ids(&db, statement).to_string(&db))
+            .collect_vec(),
+        vec![
+            "felt252_add(0, 1) -> (2)",
+            "store_temp<felt252>(2) -> (2)",
+            "felt252_add(2, 3) -> (4)",
+            "store_temp<felt252>(4) -> (4)",
+            "felt252_add(4, 8) -> (5)",
+            "store_local<felt252>(105, 5) -> (5)",
+            "felt252_add(5, 9) -> (6)",
+            "store_temp<felt252>(6) -> (6)",
+            "felt252_add3(6) -> (7)",
+            "return()",
+        ]
+    );
+    assert_eq!(
+        stats
+            .libfunc_store_counts
+            .iter()
+            .map(|(libfunc_id, count)| {
+                (
+                    db.lookup_intern_concrete_lib_func(libfunc_id.clone()).generic_id.0.to_string(),
+                    *count,
+                )
+            })
+            .collect_vec(),
+        vec![("felt252_add".to_string(), 2), ("felt252_add3".to_string(), 1)]
+    );
+    assert!(stats.unused_local_variables.is_empty());
+}
+
+/// Tests that unused local variables are reported, where a local variable is used once it is stored
+/// into its allocated space.
+#[test]
+fn unused_local_variables() {
+    let db = SierraGenDatabaseForTesting::default();
+    let statements: Vec<pre_sierra::Statement> = vec![
+        dummy_simple_statement(&db, "function_call4", &[], &["0", "1", "2", "3"]),
+        dummy_push_values(&db, &[("2", "102"), ("3", "103")]),
+        dummy_simple_statement(&db, "revoke_ap", &[], &[]),
+        dummy_return_statement(&["102", "103"]),
+    ];
+
+    let StoreOutput { statements, stats, .. } = add_store_statements_with_config(
+        &db,
+        statements,
+        &(|libfunc| LibfuncInfo { signature: get_lib_func_signature(&db, libfunc) }),
+        OrderedHashMap::from_iter(vec![("102".into(), "202".into()), ("5".into(), "205".into())]),
+        &[],
+        StoreVariablesConfig { collect_stats: true, ..Default::default() },
+    )
+    .unwrap();
+    assert_eq!(
+        statements
+            .iter()
+            .map(|statement| replace_sierra_ids(&db, statement).to_string(&db))
+            .collect_vec(),
+        vec![
+            "function_call4() -> (0, 1, 2, 3)",
+            "rename<felt252>(2) -> (102)",
+            "rename<felt252>(3) -> (103)",
+            "store_local<felt252>(202, 102) -> (102)",
+            "revoke_ap() -> ()",
+            "return(102, 103)",
+        ]
+    );
+    assert_eq!(stats.unused_local_variables, OrderedHashSet::from_iter(as_var_id_vec(&["5"])));
+}
+
+/// Tests that the stores are counted by the types of the stored variables.
+#[test]
+fn store_stats_type_store_counts() {
+    let db = SierraGenDatabaseForTesting::default();
+    let statements: Vec<pre_sierra::Statement> = vec![
+        dummy_simple_statement(&db, "felt252_add", &["0", "1"], &["2"]),
+        dummy_simple_statement(&db, "array_append", &["3", "4"], &["5"]),
+        dummy_simple_statement(&db, "felt252_add", &["6", "7"], &["8"]),
+        dummy_simple_branch(&db, "branch", &[], 0),
+        dummy_label(&db, 0),
+        dummy_return_statement(&[]),
+    ];
+
+    let StoreOutput { stats, .. } = add_store_statements_with_config(
+        &db,
+        statements,
+        &(|libfunc| LibfuncInfo { signature: get_lib_func_signature(&db, libfunc) }),
+        OrderedHashMap::from_iter(vec![("5".into(), "105".into())]),
+        &felt252_params(&db, &["0", "1", "3", "4", "6", "7"]),
+        StoreVariablesConfig { collect_stats: true, ..Default::default() },
+    )
+    .unwrap();
+    let felt252_ty =
+        db.get_concrete_type_id(db.core_felt252_ty()).expect("Can't find core::felt252.");
+    let array_ty = db
+        .get_concrete_type_id(get_core_ty_by_name(
+            db.upcast(),
+            "Array".into(),
+            vec![GenericArgumentId::Type(db.core_felt252_ty())],
+        ))
+        .expect("Can't find core::Array<core::felt252>.");
+    // `2` and `8` are stored as temporary variables, and `5` as a local variable.
+    assert_eq!(stats.type_store_counts.len(), 2);
+    assert_eq!(stats.type_store_counts.get(&felt252_ty), Some(&2));
+    assert_eq!(stats.type_store_counts.get(&array_ty), Some(&1));
+}
+
+/// Tests that pushed values that are already on the stack are counted apart from the pushed values
+/// that are stored again, including deferred values that are stored as local variables first.
+#[test]
+fn store_stats_push_prefix() {
+    let db = SierraGenDatabaseForTesting::default();
+    let statements: Vec<pre_sierra::Statement> = vec![
+        dummy_simple_statement(&db, "function_call4", &[], &["0", "1", "2", "3"]),
+        dummy_push_values(&db, &[("2", "100"), ("3", "101"), ("0", "102")]),
+        dummy_return_statement(&["100", "101", "102"]),
+    ];
+
+    let StoreOutput { statements, stats, .. } = add_store_statements_with_config(
+        &db,
+        statements,
+        &(|libfunc| LibfuncInfo { signature: get_lib_func_signature(&db, libfunc) }),
+        LocalVariables::default(),
+        &[],
+        StoreVariablesConfig { collect_stats: true, ..Default::default() },
+    )
+    .unwrap();
+    assert_eq!(
+        statements
+            .iter()
+            .map(|statement| replace_sierra_ids(&db, statement).to_string(&db))
+            .collect_vec(),
+        vec![
+            "function_call4() -> (0, 1, 2, 3)",
+            "rename<felt252>(2) -> (100)",
+            "rename<felt252>(3) -> (101)",
+            "store_temp<felt252>(0) -> (102)",
+            "return(100, 101, 102)",
+        ]
+    );
+    assert_eq!((stats.push_renamed, stats.push_restored), (2, 1));
+
+    // A deferred value that is stored as a local variable is restored from it.
+    let statements: Vec<pre_sierra::Statement> = vec![
+        dummy_simple_statement(&db, "felt252_add", &["0", "1"], &["2"]),
+        dummy_push_values(&db, &[("2", "100")]),
+        dummy_return_statement(&["100"]),
+    ];
+    let StoreOutput { statements, stats, .. } = add_store_statements_with_config(
+        &db,
+        statements,
+        &(|libfunc| LibfuncInfo { signature: get_lib_func_signature(&db, libfunc) }),
+        OrderedHashMap::from_iter(vec![("2".into(), "200".into())]),
+        &felt252_params(&db, &["0", "1"]),
+        StoreVariablesConfig { collect_stats: true, ..Default::default() },
+    )
+    .unwrap();
+    assert_eq!(
+        statements
+            .iter()
+            .map(|statement| replace_sierra_ids(&db, statement).to_string(&db))
+            .collect_vec(),
+        vec![
+            "felt252_add(0, 1) -> (2)",
+            "store_local<felt252>(200, 2) -> (2)",
+            "store_temp<felt252>(2) -> (100)",
+            "return(100)",
+        ]
+    );
+    assert_eq!((stats.push_renamed, stats.push_restored), (0, 1));
+}
+
+/// Tests that the net number of added statements is the difference between the lengths of the
+/// returned and the given statements.
+#[test]
+fn store_stats_statements_added() {
+    let db = SierraGenDatabaseForTesting::default();
+    let statements: Vec<pre_sierra::Statement> = vec![
+        dummy_simple_statement(&db, "felt252_add", &["0", "1"], &["2"]),
+        dummy_simple_statement(&db, "nope", &[], &[]),
+        dummy_simple_statement(&db, "felt252_add", &["2", "3"], &["4"]),
+        dummy_push_values(&db, &[("4", "100")]),
+        dummy_return_statement(&["100"]),
+    ];
+    let input_len = statements.len();
+
+    let StoreOutput { statements, stats, .. } = add_store_statements_with_config(
+        &db,
+        statements,
+        &(|libfunc| LibfuncInfo { signature: get_lib_func_signature(&db, libfunc) }),
+        LocalVariables::default(),
+        &felt252_params(&db, &["0", "1", "3"]),
+        StoreVariablesConfig { collect_stats: true, ..Default::default() },
+    )
+    .unwrap();
+    assert_eq!(stats.input_statement_count, input_len);
+    assert_eq!(stats.output_statement_count, statements.len());
+    assert_eq!(stats.statements_added(), statements.len() as isize - input_len as isize);
+    // `2` and `4` are stored, and the push is replaced by the store of `4`.
+    assert_eq!(stats.statements_added(), 1);
+}
+
+/// Tests that the peak number of local variables accounts for local variables that are no longer
+/// used.
+#[test]
+fn store_stats_peak_locals() {
+    let db = SierraGenDatabaseForTesting::default();
+    let statements: Vec<pre_sierra::Statement> = vec![
+        dummy_simple_statement(&db, "function_call4", &[], &["0", "1", "2", "3"]),
+        // `0` and `1` are local variables at the same time.
+        dummy_simple_statement(&db, "revoke_ap", &[], &[]),
+        dummy_simple_statement(&db, "felt252_add", &["0", "1"], &["4"]),
+        // `4` is a local variable only after `0` and `1` are consumed.
+        dummy_simple_statement(&db, "revoke_ap", &[], &[]),
+        dummy_return_statement(&[]),
+    ];
+
+    let StoreOutput { statements, stats, .. } = add_store_statements_with_config(
+        &db,
+        statements,
+        &(|libfunc| LibfuncInfo { signature: get_lib_func_signature(&db, libfunc) }),
+        OrderedHashMap::from_iter(vec![
+            ("0".into(), "100".into()),
+            ("1".into(), "101".into()),
+            ("4".into(), "104".into()),
+        ]),
+        &[],
+        StoreVariablesConfig { collect_stats: true, ..Default::default() },
+    )
+    .unwrap();
+    assert_eq!(
+        statements
+            .iter()
+            .map(|statement| replace_sierra_ids(&db, statement).to_string(&db))
+            .collect_vec(),
+        vec![
+            "function_call4() -> (0, 1, 2, 3)",
+            "store_local<felt252>(100, 0) -> (0)",
+            "store_local<felt252>(101, 1) -> (1)",
+            "revoke_ap() -> ()",
+            "felt252_add(0, 1) -> (4)",
+            "store_local<felt252>(104, 4) -> (4)",
+            "revoke_ap() -> ()",
+            "return()",
+        ]
+    );
+    assert_eq!(stats.peak_locals, 2);
+}
+
+/// Tests that collecting the statistics does not change the added statements, and that the used
+/// local variables are reported.
+#[test]
+fn store_output() {
+    let db = SierraGenDatabaseForTesting::default();
+    let statements: Vec<pre_sierra::Statement> = vec![
+        dummy_simple_statement(&db, "function_call4", &[], &["0", "1", "2", "3"]),
+        dummy_push_values(&db, &[("2", "102"), ("3", "103")]),
+        dummy_simple_statement(&db, "revoke_ap", &[], &[]),
+        dummy_return_statement(&["102", "103"]),
+    ];
+    let local_variables: LocalVariables =
+        OrderedHashMap::from_iter(vec![("2".into(), "202".into()), ("5".into(), "205".into())]);
+    let get_libfunc_info =
+        |libfunc| LibfuncInfo { signature: get_lib_func_signature(&db, libfunc) };
+
+    let output = add_store_statements_with_config(
+        &db,
+        statements.clone(),
+        &get_libfunc_info,
+        local_variables.clone(),
+        &[],
+        StoreVariablesConfig { collect_stats: true, ..Default::default() },
+    )
+    .unwrap();
+    assert_eq!(
+        output.statements,
+        add_store_statements(
+            &db,
+            statements.clone(),
+            &get_libfunc_info,
+            local_variables.clone(),
+            &[]
+        )
+    );
+    let full = add_store_statements_full(&db, statements, &get_libfunc_info, local_variables, &[]);
+    assert_eq!(
+        (&full.statements, &full.used_locals, &full.stats),
+        (&output.statements, &output.used_locals, &output.stats)
+    );
+    assert_eq!(
+        output.stats.unused_local_variables,
+        OrderedHashSet::from_iter(as_var_id_vec(&["5"]))
+    );
+    // `2` is stored into its allocated space before the unknown ap change.
+    assert_eq!(output.used_locals, OrderedHashSet::from_iter(as_var_id_vec(&["2"])));
+}
+
+/// Tests that the deferred arguments of a libfunc are stored if it is forced to get stored
+/// arguments, and are kept deferred otherwise.
+#[test]
+fn forced_stores() {
+    let db = SierraGenDatabaseForTesting::default();
+    let dup_statement = dummy_simple_statement(&db, "dup", &["2"], &["3", "4"]);
+    let pre_sierra::Statement::Sierra(GenStatement::Invocation(dup_invocation)) = &dup_statement
+    else {
+        panic!("Expected an invocation.");
+    };
+    let dup_libfunc_id = dup_invocation.libfunc_id.clone();
+    let statements: Vec<pre_sierra::Statement> = vec![
+        dummy_simple_statement(&db, "felt252_add", &["0", "1"], &["2"]),
+        dup_statement.clone(),
+        dummy_return_statement(&[]),
+    ];
+    let run = |force_store_libfuncs: &[ConcreteLibfuncId]| {
+        let output = add_store_statements_with_config(
+            &db,
+            statements.clone(),
+            &(|libfunc| LibfuncInfo { signature: get_lib_func_signature(&db, libfunc) }),
+            LocalVariables::default(),
+            &felt252_params(&db, &["0", "1"]),
+            StoreVariablesConfig {
+                force_store_libfuncs: force_store_libfuncs.iter().cloned().collect(),
+                collect_stats: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        (
+            output
+                .statements
+                .iter()
+                .map(|statement| replace_sierra_ids(&db, statement).to_string(&db))
+                .collect_vec(),
+            output.stats.libfunc_store_counts,
+        )
+    };
+
+    let (statements, libfunc_store_counts) = run(&[]);
+    assert_eq!(statements, vec!["felt252_add(0, 1) -> (2)", "dup(2) -> (3, 4)", "return()"]);
+    assert!(libfunc_store_counts.is_empty());
+    // The forced store is not caused by the signature of `dup`, so it is not counted.
+    let (statements, libfunc_store_counts) = run(&[dup_libfunc_id]);
+    assert_eq!(
+        statements,
+        vec![
+            "felt252_add(0, 1) -> (2)",
+            "store_temp<felt252>(2) -> (2)",
+            "dup(2) -> (3, 4)",
+            "return()",
+        ]
+    );
+    assert!(libfunc_store_counts.is_empty());
+}
+
+#[test]
+fn insertion_indices() {
+    let db = SierraGenDatabaseForTesting::default();
+    let run = || {
+        let statements: Vec<pre_sierra::Statement> = vec![
+            dummy_simple_statement(&db, "felt252_add", &["0", "1"], &["2"]),
+            dummy_simple_statement(&db, "felt252_add", &["2", "3"], &["4"]),
+            dummy_simple_statement(&db, "function_call4", &[], &["5", "6", "7", "8"]),
+            dummy_push_values(&db, &[("7", "107"), ("8", "108"), ("4", "104")]),
+            dummy_return_statement(&["107", "108", "104"]),
+        ];
+        let output = add_store_statements_with_config(
+            &db,
+            statements,
+            &(|libfunc| LibfuncInfo { signature: get_lib_func_signature(&db, libfunc) }),
+            LocalVariables::default(),
+            &felt252_params(&db, &["0", "1", "3"]),
+            StoreVariablesConfig { collect_insertion_indices: true, ..Default::default() },
+        )
+        .unwrap();
+        (output.statements, output.insertion_indices)
+    };
+
+    let (statements, indices) = run();
+    assert_eq!(
+        statements
+            .iter()
+            .map(|statement| replace_sierra_ids(&db, statement).to_string(&db))
+            .collect_vec(),
+        vec![
+            "felt252_add(0, 1) -> (2)",
+            "store_temp<felt252>(2) -> (2)",
+            "felt252_add(2, 3) -> (4)",
+            "function_call4() -> (5, 6, 7, 8)",
+            "rename<felt252>(7) -> (107)",
+            "rename<felt252>(8) -> (108)",
+            "store_temp<felt252>(4) -> (104)",
+            "return(107, 108, 104)",
+        ]
+    );
+    assert_eq!(indices, vec![None, Some(0), None, None, Some(1), Some(2), Some(3), None]);
+    assert_eq!(run(), (statements, indices));
+}
+
+#[test]
+fn branch_stores() {
+    let db = SierraGenDatabaseForTesting::default();
+    let statements: Vec<pre_sierra::Statement> = vec![
+        dummy_simple_statement(&db, "felt252_add", &["0", "1"], &["2"]),
+        dummy_simple_statement(&db, "felt252_add", &["2", "4"], &["3"]),
+        dummy_simple_branch(&db, "branch", &[], 0),
+        dummy_simple_branch(&db, "branch", &[], 0),
+        dummy_label(&db, 0),
+        dummy_return_statement(&[]),
+    ];
+
+    let StoreOutput { statements, branch_stores: branch_store_infos, .. } =
+        add_store_statements_with_config(
+            &db,
+            statements,
+            &(|libfunc| LibfuncInfo { signature: get_lib_func_signature(&db, libfunc) }),
+            LocalVariables::default(),
+            &felt252_params(&db, &["0", "1", "4"]),
+            StoreVariablesConfig { collect_branch_stores: true, ..Default::default() },
+        )
+        .unwrap();
+    assert_eq!(
+        statements
+            .iter()
+            .map(|statement| replace_sierra_ids(&db, statement).to_string(&db))
+            .collect_vec(),
+        vec![
+            "felt252_add(0, 1) -> (2)",
+            "store_temp<felt252>(2) -> (2)",
+            "felt252_add(2, 4) -> (3)",
+            "store_temp<felt252>(3) -> (3)",
+            "branch() { label_test::test::0() fallthrough() }",
+            "branch() { label_test::test::0() fallthrough() }",
+            "label_test::test::0:",
+            "return()",
+        ]
+    );
+    assert_eq!(
+        branch_store_infos,
+        vec![
+            BranchStoreInfo {
+                statement_idx: 2,
+                stores_before_branch: 1,
+                stores_per_branch: vec![0, 0]
+            },
+            BranchStoreInfo {
+                statement_idx: 3,
+                stores_before_branch: 0,
+                stores_per_branch: vec![0, 0]
+            },
+        ]
+    );
+}
+
+#[test]
+fn state_snapshots() {
+    let db = SierraGenDatabaseForTesting::default();
+    let felt252_ty =
+        db.get_concrete_type_id(db.core_felt252_ty()).expect("Can't find core::felt252.");
+    let statements: Vec<pre_sierra::Statement> = vec![
+        dummy_simple_statement(&db, "felt252_add", &["0", "1"], &["2"]),
+        dummy_simple_statement(&db, "felt252_add", &["2", "3"], &["4"]),
+        dummy_return_statement(&[]),
+    ];
+
+    let get_libfunc_info =
+        |libfunc| LibfuncInfo { signature: get_lib_func_signature(&db, libfunc) };
+    let StoreOutput { statements: output_statements, state_snapshots, .. } =
+        add_store_statements_with_config(
+            &db,
+            statements.clone(),
+            &get_libfunc_info,
+            LocalVariables::default(),
+            &felt252_params(&db, &["0", "1", "3"]),
+            StoreVariablesConfig { collect_state_snapshots: true, ..Default::default() },
+        )
+        .unwrap();
+    assert_eq!(
+        add_store_statements_debug(
+            &db,
+            statements,
+            &get_libfunc_info,
+            LocalVariables::default(),
+            &as_var_id_vec(&["0", "1", "3"]),
+        ),
+        (output_statements, state_snapshots.clone())
+    );
+    let deferred = VarState::Deferred {
+        info: DeferredVariableInfo { ty: felt252_ty, kind: DeferredVariableKind::Generic },
+    };
+    assert_eq!(
+        state_snapshots,
+        vec![
+            StateSnapshot::Reachable {
+                variables: OrderedHashMap::from_iter([
+                    ("3".into(), VarState::LocalVar),
+                    ("2".into(), deferred.clone()),
+                ]),
+                known_stack: vec![],
+            },
+            StateSnapshot::Reachable {
+                variables: OrderedHashMap::from_iter([("4".into(), deferred)]),
+                known_stack: as_var_id_vec(&["2"]),
+            },
+            StateSnapshot::Unreachable,
+        ]
+    );
+}
+
+/// Tests that a param that is marked as a local variable is stored once, right after its space is
+/// allocated.
+#[test]
+fn local_param() {
+    let db = SierraGenDatabaseForTesting::default();
+    let felt252_ty =
+        db.get_concrete_type_id(db.core_felt252_ty()).expect("Can't find core::felt252.");
+    let params = as_var_id_vec(&["0", "1"])
+        .into_iter()
+        .map(|id| Param { id, ty: felt252_ty.clone() })
+        .collect_vec();
+
+    let statements: Vec<pre_sierra::Statement> = vec![
+        dummy_simple_statement(&db, "alloc_local", &[], &["100"]),
+        dummy_simple_statement(&db, "revoke_ap", &[], &[]),
+        dummy_simple_statement(&db, "felt252_add", &["0", "1"], &["2"]),
+        dummy_simple_statement(&db, "revoke_ap", &[], &[]),
+        dummy_return_statement(&[]),
+    ];
+    let statements = add_store_statements_with_config(
+        &db,
+        statements,
+        &(|libfunc| LibfuncInfo { signature: get_lib_func_signature(&db, libfunc) }),
+        OrderedHashMap::from_iter(vec![("0".into(), "100".into())]),
+        &params,
+        StoreVariablesConfig::default(),
+    )
+    .unwrap()
+    .statements;
+    assert_eq!(
+        statements
+            .iter()
+            .map(|statement| replace_sierra_ids(&db, statement).to_string(&db))
+            .collect_vec(),
+        vec![
+            "alloc_local() -> (100)",
+            "store_local<felt252>(100, 0) -> (0)",
+            "revoke_ap() -> ()",
+            "felt252_add(0, 1) -> (2)",
+            "revoke_ap() -> ()",
+            "return()",
+        ]
+    );
+}
+
+/// Tests that a param that is marked as a local variable is not stored by [add_store_statements],
+/// as opposed to [add_store_statements_with_config].
+#[test]
+fn local_param_default() {
+    let db = SierraGenDatabaseForTesting::default();
+    let statements: Vec<pre_sierra::Statement> = vec![
+        dummy_simple_statement(&db, "alloc_local", &[], &["100"]),
+        dummy_simple_statement(&db, "revoke_ap", &[], &[]),
+        dummy_simple_statement(&db, "felt252_add", &["0", "1"], &["2"]),
+        dummy_simple_statement(&db, "revoke_ap", &[], &[]),
+        dummy_return_statement(&[]),
+    ];
+    assert_eq!(
+        test_add_store_statements(
+            &db,
+            statements,
+            OrderedHashMap::from_iter(vec![("0".into(), "100".into())]),
+            &["0", "1"]
+        ),
+        vec![
+            "alloc_local() -> (100)",
+            "revoke_ap() -> ()",
+            "felt252_add(0, 1) -> (2)",
+            "revoke_ap() -> ()",
+            "return()",
+        ]
+    );
+}
+
+#[test]
+fn missing_var_error() {
+    let db = SierraGenDatabaseForTesting::default();
+    let statements: Vec<pre_sierra::Statement> = vec![
+        dummy_simple_statement(&db, "nope", &[], &[]),
+        dummy_simple_statement(&db, "felt252_add", &["0", "1"], &["2"]),
+        dummy_push_values(&db, &[("2", "100"), ("3", "101")]),
+        dummy_return_statement(&["100", "101"]),
+    ];
+
+    assert_eq!(
+        add_store_statements_with_config(
+            &db,
+            statements,
+            &(|libfunc| LibfuncInfo { signature: get_lib_func_signature(&db, libfunc) }),
+            LocalVariables::default(),
+            &felt252_params(&db, &["0", "1"]),
+            StoreVariablesConfig {
+                missing_var_policy: MissingVarPolicy::Error,
+                ..Default::default()
+            }
+        )
+        .map(|output| output.statements),
+        Err(StoreError::MissingVar { var: "3".into(), at_statement: 2 })
+    );
+}
+
+/// Tests that an error is returned once a `store_temp` statement places a value deeper in the known
+/// stack than allowed.
+#[test]
+fn max_ap_offset() {
+    let db = SierraGenDatabaseForTesting::default();
+    let felt252_ty =
+        db.get_concrete_type_id(db.core_felt252_ty()).expect("Can't find core::felt252.");
+    let params = as_var_id_vec(&["0", "1", "2"])
+        .into_iter()
+        .map(|id| Param { id, ty: felt252_ty.clone() })
+        .collect_vec();
+    let statements = || -> Vec<pre_sierra::Statement> {
+        vec![
+            dummy_simple_statement(&db, "nope", &[], &[]),
+            dummy_push_values(&db, &[("0", "100"), ("1", "101"), ("2", "102")]),
+            dummy_return_statement(&["100", "101", "102"]),
+        ]
+    };
+    let try_add_with_limit = |limit| {
+        add_store_statements_with_config(
+            &db,
+            statements(),
+            &(|libfunc| LibfuncInfo { signature: get_lib_func_signature(&db, libfunc) }),
+            LocalVariables::default(),
+            &params,
+            StoreVariablesConfig { max_ap_offset: Some(limit), ..Default::default() },
+        )
+        .map(|output| output.statements)
+    };
+
+    assert_eq!(
+        try_add_with_limit(2),
+        Err(StoreError::ApOffsetExceeded {
+            var: "102".into(),
+            offset: 3,
+            limit: 2,
+            at_statement: 1
+        })
+    );
+    assert_eq!(
+        try_add_with_limit(3)
+            .unwrap()
+            .iter()
+            .map(|statement| replace_sierra_ids(&db, statement).to_string(&db))
+            .collect_vec(),
+        vec![
+            "nope() -> ()",
+            "store_temp<felt252>(0) -> (100)",
+            "store_temp<felt252>(1) -> (101)",
+            "store_temp<felt252>(2) -> (102)",
+            "return(100, 101, 102)",
+        ]
+    );
+}
+
+/// Tests that an error is returned once more statements are required than allowed.
+#[test]
+fn max_inserted() {
+    let db = SierraGenDatabaseForTesting::default();
+    let statements = || -> Vec<pre_sierra::Statement> {
+        vec![
+            dummy_simple_statement(&db, "function_call4", &[], &["0", "1", "2", "3"]),
+            dummy_push_values(&db, &[("0", "100"), ("1", "101"), ("2", "102")]),
+            dummy_return_statement(&["100", "101", "102"]),
+        ]
+    };
+    let try_add_with_limit = |limit| {
+        add_store_statements_with_config(
+            &db,
+            statements(),
+            &(|libfunc| LibfuncInfo { signature: get_lib_func_signature(&db, libfunc) }),
+            LocalVariables::default(),
+            &[],
+            StoreVariablesConfig { max_inserted: Some(limit), ..Default::default() },
+        )
+        .map(|output| output.statements)
+    };
+
+    assert_eq!(try_add_with_limit(2), Err(StoreError::LimitExceeded { inserted: 3, limit: 2 }));
+    assert_eq!(
+        try_add_with_limit(3)
+            .unwrap()
+            .iter()
+            .map(|statement| replace_sierra_ids(&db, statement).to_string(&db))
+            .collect_vec(),
+        vec![
+            "function_call4() -> (0, 1, 2, 3)",
+            "store_temp<felt252>(0) -> (100)",
+            "store_temp<felt252>(1) -> (101)",
+            "store_temp<felt252>(2) -> (102)",
+            "return(100, 101, 102)",
+        ]
+    );
+
+    // The limit is reported as soon as it is exceeded, even within a single statement.
+    let mut handler = AddStoreVariableStatements::new(&db, LocalVariables::default(), vec![]);
+    handler.config.max_inserted = Some(2);
+    let results = statements()
+        .into_iter()
+        .take(2)
+        .map(|statement| {
+            handler.handle_statement(statement, &|libfunc| LibfuncInfo {
+                signature: get_lib_func_signature(&db, libfunc),
+            })
+        })
+        .collect_vec();
+    assert_eq!(results, vec![Ok(()), Err(StoreError::LimitExceeded { inserted: 3, limit: 2 })]);
+    assert_eq!(
+        handler
+            .result
+            .iter()
+            .map(|statement| replace_sierra_ids(&db, statement).to_string(&db))
+            .collect_vec(),
+        vec![
+            "function_call4() -> (0, 1, 2, 3)",
+            "store_temp<felt252>(0) -> (100)",
+            "store_temp<felt252>(1) -> (101)",
+        ]
+    );
+    assert_eq!(handler.inserted_count, 2);
+}
+
+/// Tests that a required `dup` statement is an error when [StoreVariablesConfig::forbid_dup] is
+/// set, and that statements requiring no `dup` statements are unaffected by it.
+#[test]
+fn store_variables_forbid_dup() {
+    let db = SierraGenDatabaseForTesting::default();
+    let statements = |dup| {
+        vec![
+            dummy_simple_statement(&db, "function_call4", &[], &["0", "1", "2", "3"]),
+            dummy_push_values_ex(&db, &[("0", "100", dup), ("1", "101", false)]),
+            dummy_return_statement(&["100", "101"]),
+        ]
+    };
+    let get_lib_func_info =
+        |libfunc| LibfuncInfo { signature: get_lib_func_signature(&db, libfunc) };
+    let try_add_forbidding_dup = |statements| {
+        add_store_statements_with_config(
+            &db,
+            statements,
+            &get_lib_func_info,
+            LocalVariables::default(),
+            &[],
+            StoreVariablesConfig { forbid_dup: true, ..Default::default() },
+        )
+        .map(|output| output.statements)
+    };
+
+    assert_eq!(
+        try_add_forbidding_dup(statements(true)),
+        Err(StoreError::UnexpectedDup { var: "0".into() })
+    );
+    assert_eq!(
+        try_add_forbidding_dup(statements(false)),
+        Ok(add_store_statements_with_config(
+            &db,
+            statements(false),
+            &get_lib_func_info,
+            LocalVariables::default(),
+            &[],
+            StoreVariablesConfig::default()
+        )
+        .unwrap()
+        .statements)
+    );
 }
 
 #[test]
-fn store_temp_simple() {
+fn store_eagerly() {
     let db = SierraGenDatabaseForTesting::default();
     let statements: Vec<pre_sierra::Statement> = vec![
-        dummy_simple_statement(&db, "felt252_add", &["0", "1"], &["2"]),
-        dummy_simple_statement(&db, "nope", &[], &[]),
-        dummy_simple_statement(&db, "felt252_add", &["2", "3"], &["4"]),
-        dummy_simple_statement(&db, "nope", &[], &[]),
-        dummy_simple_statement(&db, "felt252_add", &["5", "4"], &["5"]),
-        dummy_simple_statement(&db, "nope", &[], &[]),
+        dummy_simple_statement(&db, "felt252_const", &[], &["0"]),
+        dummy_simple_statement(&db, "felt252_add", &["1", "0"], &["2"]),
+        dummy_simple_branch(&db, "branch", &[], 0),
+        dummy_simple_statement(&db, "felt252_add3", &["4"], &["3"]),
         dummy_label(&db, 0),
-        dummy_simple_statement(&db, "felt252_add", &["5", "6"], &["6"]),
-        dummy_return_statement(&[]),
+        dummy_push_values(&db, &[("2", "100")]),
+        dummy_return_statement(&["100"]),
     ];
 
+    let mut handler = AddStoreVariableStatements::new(
+        &db,
+        LocalVariables::default(),
+        vec![("1".into(), VarState::LocalVar), ("4".into(), VarState::LocalVar)],
+    );
+    handler.config.store_policy = StorePolicy::StoreEagerly;
+    handler.state_snapshots = Some(vec![]);
+    let handler = super::handle_statements(
+        handler,
+        statements,
+        &(|libfunc| LibfuncInfo { signature: get_lib_func_signature(&db, libfunc) }),
+    );
     assert_eq!(
-        test_add_store_statements(
-            &db,
-            statements,
-            LocalVariables::default(),
-            &["0", "1", "3", "5", "6"]
-        ),
+        handler
+            .result
+            .iter()
+            .map(|statement| replace_sierra_ids(&db, statement).to_string(&db))
+            .collect_vec(),
         vec![
-            "felt252_add(0, 1) -> (2)",
-            "nope() -> ()",
+            "felt252_const() -> (0)",
+            "store_temp<felt252>(0) -> (0)",
+            "felt252_add(1, 0) -> (2)",
             "store_temp<felt252>(2) -> (2)",
-            "felt252_add(2, 3) -> (4)",
-            "nope() -> ()",
-            "store_temp<felt252>(4) -> (4)",
-            "felt252_add(5, 4) -> (5)",
-            "nope() -> ()",
+            "branch() { label_test::test::0() fallthrough() }",
+            "felt252_add3(4) -> (3)",
+            "store_temp<felt252>(3) -> (3)",
             "label_test::test::0:",
-            "store_temp<felt252>(5) -> (5)",
-            "felt252_add(5, 6) -> (6)",
-            "return()",
+            "store_temp<felt252>(2) -> (100)",
+            "return(100)",
         ]
     );
+    for state_snapshot in handler.state_snapshots.unwrap() {
+        if let StateSnapshot::Reachable { variables, .. } = state_snapshot {
+            assert!(
+                variables.values().all(|var_state| !matches!(var_state, VarState::Deferred { .. })),
+                "Found a deferred variable: {variables:?}"
+            );
+        }
+    }
 }
 
+/// Tests that with [StorePolicy::StoreAllBeforeBranch], all the variables are stored before a
+/// branch, including deferred constants, which are otherwise kept deferred.
 #[test]
-fn store_temp_for_branch_command() {
+fn store_all_before_branch() {
     let db = SierraGenDatabaseForTesting::default();
     let statements: Vec<pre_sierra::Statement> = vec![
-        dummy_simple_statement(&db, "felt252_add", &["0", "1"], &["2"]),
-        dummy_simple_branch(&db, "branch_with_param", &["2"], 0),
+        dummy_simple_statement(&db, "felt252_const", &[], &["0"]),
+        dummy_simple_statement(&db, "felt252_add", &["1", "2"], &["3"]),
+        dummy_simple_branch(&db, "branch", &[], 0),
         dummy_label(&db, 0),
         dummy_return_statement(&[]),
     ];
 
+    let mut handler = AddStoreVariableStatements::new(
+        &db,
+        LocalVariables::default(),
+        vec![("1".into(), VarState::LocalVar), ("2".into(), VarState::LocalVar)],
+    );
+    handler.config.store_policy = StorePolicy::StoreAllBeforeBranch;
+    handler.state_snapshots = Some(vec![]);
+    let handler = super::handle_statements(
+        handler,
+        statements,
+        &(|libfunc| LibfuncInfo { signature: get_lib_func_signature(&db, libfunc) }),
+    );
     assert_eq!(
-        test_add_store_statements(&db, statements, LocalVariables::default(), &["0", "1"]),
+        handler
+            .result
+            .iter()
+            .map(|statement| replace_sierra_ids(&db, statement).to_string(&db))
+            .collect_vec(),
         vec![
-            "felt252_add(0, 1) -> (2)",
-            "store_temp<felt252>(2) -> (2)",
-            "branch_with_param(2) { label_test::test::0() fallthrough() }",
+            "felt252_const() -> (0)",
+            "felt252_add(1, 2) -> (3)",
+            "store_temp<felt252>(0) -> (0)",
+            "store_temp<felt252>(3) -> (3)",
+            "branch() { label_test::test::0() fallthrough() }",
             "label_test::test::0:",
             "return()",
         ]
     );
+    // The state after the branch.
+    let StateSnapshot::Reachable { variables, known_stack } = &handler.state_snapshots.unwrap()[2]
+    else {
+        panic!("Expected a reachable state after the branch.");
+    };
+    for (var, var_state) in variables.iter() {
+        match var_state {
+            VarState::TempVar { .. } => {
+                assert!(known_stack.contains(var), "`{var}` is not on the known stack.")
+            }
+            VarState::Deferred { .. } => panic!("`{var}` is deferred."),
+            VarState::LocalVar => {}
+        }
+    }
 }
 
 #[test]
-fn store_local_simple() {
+fn requires_stores_test() {
     let db = SierraGenDatabaseForTesting::default();
+    let get_libfunc_info =
+        |libfunc| LibfuncInfo { signature: get_lib_func_signature(&db, libfunc) };
+
     let statements: Vec<pre_sierra::Statement> = vec![
         dummy_simple_statement(&db, "felt252_add", &["0", "1"], &["2"]),
         dummy_simple_statement(&db, "nope", &[], &[]),
-        // Case I: local added instead of tempvar, when first used.
+        dummy_return_statement(&[]),
+    ];
+    assert!(!requires_stores(
+        &db,
+        statements,
+        &get_libfunc_info,
+        LocalVariables::default(),
+        &as_var_id_vec(&["0", "1"]),
+    ));
+
+    let statements: Vec<pre_sierra::Statement> = vec![
+        dummy_simple_statement(&db, "felt252_add", &["0", "1"], &["2"]),
         dummy_simple_statement(&db, "felt252_add", &["2", "3"], &["4"]),
-        dummy_simple_statement(&db, "nope", &[], &[]),
-        // Case II: deferred computed into local before revoke_ap().
-        dummy_simple_statement(&db, "revoke_ap", &[], &[]),
-        dummy_simple_statement(&db, "function_call4", &[], &["5", "6", "7", "8"]),
-        dummy_simple_statement(&db, "nope", &[], &[]),
-        // Case III: tempvar copied into local before revoke_ap().
-        dummy_simple_statement(&db, "revoke_ap", &[], &[]),
-        dummy_simple_statement(&db, "store_temp<felt252>", &["9"], &["9"]),
-        // Don't store as local due to a simple jump.
-        dummy_jump_statement(&db, 0),
-        dummy_label(&db, 0),
-        dummy_simple_statement(&db, "nope", &[], &[]),
-        // Case IV: tempvar copied into local before branches.
-        dummy_simple_branch(&db, "branch", &[], 1),
-        dummy_label(&db, 1),
         dummy_return_statement(&[]),
     ];
+    assert!(requires_stores(
+        &db,
+        statements,
+        &get_libfunc_info,
+        LocalVariables::default(),
+        &as_var_id_vec(&["0", "1", "3"]),
+    ));
+}
+
+/// Tests that the stores of params that are marked as local variables are grouped, with
+/// [StoreVariablesConfig::hoist_entry_locals].
+#[test]
+fn hoisted_local_params() {
+    let db = SierraGenDatabaseForTesting::default();
+    let felt252_ty =
+        db.get_concrete_type_id(db.core_felt252_ty()).expect("Can't find core::felt252.");
+    let params = as_var_id_vec(&["0", "1"])
+        .into_iter()
+        .map(|id| Param { id, ty: felt252_ty.clone() })
+        .collect_vec();
 
+    let statements: Vec<pre_sierra::Statement> = vec![
+        dummy_label(&db, 0),
+        dummy_simple_statement(&db, "alloc_local", &[], &["100"]),
+        dummy_simple_statement(&db, "alloc_local", &[], &["101"]),
+        dummy_simple_statement(&db, "felt252_add", &["0", "1"], &["2"]),
+        dummy_return_statement(&[]),
+    ];
+    let statements = add_store_statements_with_config(
+        &db,
+        statements,
+        &(|libfunc| LibfuncInfo { signature: get_lib_func_signature(&db, libfunc) }),
+        OrderedHashMap::from_iter(vec![("0".into(), "100".into()), ("1".into(), "101".into())]),
+        &params,
+        StoreVariablesConfig { hoist_entry_locals: true, ..Default::default() },
+    )
+    .unwrap()
+    .statements;
     assert_eq!(
-        test_add_store_statements(
-            &db,
-            statements,
-            OrderedHashMap::from_iter(vec![
-                ("2".into(), "102".into()),
-                ("4".into(), "104".into()),
-                ("7".into(), "107".into()),
-                ("9".into(), "109".into())
-            ]),
-            &["0", "1", "3", "9"]
-        ),
+        statements
+            .iter()
+            .map(|statement| replace_sierra_ids(&db, statement).to_string(&db))
+            .collect_vec(),
         vec![
-            "felt252_add(0, 1) -> (2)",
-            "nope() -> ()",
-            "store_local<felt252>(102, 2) -> (2)",
-            "felt252_add(2, 3) -> (4)",
-            "nope() -> ()",
-            "store_local<felt252>(104, 4) -> (4)",
-            "revoke_ap() -> ()",
-            "function_call4() -> (5, 6, 7, 8)",
-            "nope() -> ()",
-            "store_local<felt252>(107, 7) -> (7)",
-            "revoke_ap() -> ()",
-            "store_temp<felt252>(9) -> (9)",
-            "jump() { label_test::test::0() }",
             "label_test::test::0:",
-            "nope() -> ()",
-            "store_local<felt252>(109, 9) -> (9)",
-            "branch() { label_test::test::1() fallthrough() }",
-            "label_test::test::1:",
+            "alloc_local() -> (100)",
+            "alloc_local() -> (101)",
+            "store_local<felt252>(100, 0) -> (0)",
+            "store_local<felt252>(101, 1) -> (1)",
+            "felt252_add(0, 1) -> (2)",
             "return()",
-        ],
+        ]
     );
 }
 
@@ -520,6 +2064,68 @@ fn store_temp_push_values_with_dup() {
     );
 }
 
+/// Tests that a const that is pushed with a dup, but is not used afterwards, is not duplicated
+/// only if [StoreVariablesConfig::skip_unused_const_dup] is set.
+#[test]
+fn push_values_single_use_const() {
+    let db = SierraGenDatabaseForTesting::default();
+    let run = |skip_unused_const_dup| {
+        let statements: Vec<pre_sierra::Statement> = vec![
+            dummy_simple_statement(&db, "felt252_const", &[], &["0"]),
+            dummy_simple_statement(&db, "felt252_const", &[], &["1"]),
+            dummy_push_values_ex(&db, &[("0", "100", true), ("1", "101", true)]),
+            dummy_simple_statement(&db, "felt252_add", &["2", "1"], &["3"]),
+            dummy_return_statement(&["100", "101"]),
+        ];
+        add_store_statements_with_config(
+            &db,
+            statements,
+            &(|libfunc| LibfuncInfo { signature: get_lib_func_signature(&db, libfunc) }),
+            LocalVariables::default(),
+            &felt252_params(&db, &["2"]),
+            StoreVariablesConfig { skip_unused_const_dup, ..Default::default() },
+        )
+        .unwrap()
+        .statements
+        .iter()
+        .map(|statement| replace_sierra_ids(&db, statement).to_string(&db))
+        .collect_vec()
+    };
+
+    let before = run(false);
+    assert_eq!(
+        before,
+        vec![
+            "felt252_const() -> (0)",
+            "felt252_const() -> (1)",
+            "dup<felt252>(0) -> (0, 100)",
+            "store_temp<felt252>(100) -> (100)",
+            "dup<felt252>(1) -> (1, 101)",
+            "store_temp<felt252>(101) -> (101)",
+            "felt252_add(2, 1) -> (3)",
+            "return(100, 101)",
+        ]
+    );
+    let after = run(true);
+    assert_eq!(
+        after,
+        vec![
+            "felt252_const() -> (0)",
+            "felt252_const() -> (1)",
+            "store_temp<felt252>(0) -> (100)",
+            "dup<felt252>(1) -> (1, 101)",
+            "store_temp<felt252>(101) -> (101)",
+            "felt252_add(2, 1) -> (3)",
+            "return(100, 101)",
+        ]
+    );
+    // Only the `dup` of the unused const is saved. The number of stores is unchanged.
+    assert_eq!(before.len() - after.len(), 1);
+    let count_stores =
+        |statements: &[String]| statements.iter().filter(|s| s.starts_with("store_temp")).count();
+    assert_eq!(count_stores(&before), count_stores(&after));
+}
+
 /// Tests the [PushValues](pre_sierra::Statement::PushValues) optimization.
 #[test]
 fn push_values_optimization() {
@@ -544,6 +2150,153 @@ fn push_values_optimization() {
     );
 }
 
+/// Tests that every pushed value is stored when the prefix optimization is disabled.
+#[test]
+fn push_values_optimization_disabled() {
+    let db = SierraGenDatabaseForTesting::default();
+    let statements: Vec<pre_sierra::Statement> = vec![
+        dummy_simple_statement(&db, "function_call4", &[], &["0", "1", "2", "3"]),
+        dummy_push_values(&db, &[("2", "102"), ("3", "103"), ("0", "100")]),
+        dummy_push_values(&db, &[("100", "200")]),
+        dummy_return_statement(&["1"]),
+    ];
+
+    let statements = add_store_statements_with_config(
+        &db,
+        statements,
+        &(|libfunc| LibfuncInfo { signature: get_lib_func_signature(&db, libfunc) }),
+        LocalVariables::default(),
+        &[],
+        StoreVariablesConfig { disable_push_prefix_opt: true, ..Default::default() },
+    )
+    .unwrap()
+    .statements;
+    assert_eq!(
+        statements
+            .iter()
+            .map(|statement| replace_sierra_ids(&db, statement).to_string(&db))
+            .collect_vec(),
+        vec![
+            "function_call4() -> (0, 1, 2, 3)",
+            "store_temp<felt252>(2) -> (102)",
+            "store_temp<felt252>(3) -> (103)",
+            "store_temp<felt252>(0) -> (100)",
+            "store_temp<felt252>(100) -> (200)",
+            "return(1)",
+        ]
+    );
+}
+
+/// Tests that verifying the invariants of the state does not change the result of valid code.
+#[test]
+fn store_variables_with_verified_invariants() {
+    let db = SierraGenDatabaseForTesting::default();
+    let statements: Vec<pre_sierra::Statement> = vec![
+        dummy_simple_statement(&db, "felt252_add", &["0", "1"], &["2"]),
+        dummy_simple_statement(&db, "nope", &[], &[]),
+        dummy_simple_statement(&db, "felt252_add", &["2", "3"], &["4"]),
+        dummy_simple_statement(&db, "nope", &[], &[]),
+        dummy_simple_statement(&db, "felt252_add", &["5", "4"], &["5"]),
+        dummy_simple_statement(&db, "nope", &[], &[]),
+        dummy_label(&db, 0),
+        dummy_simple_statement(&db, "felt252_add", &["5", "6"], &["6"]),
+        dummy_return_statement(&[]),
+    ];
+    let felt252_ty =
+        db.get_concrete_type_id(db.core_felt252_ty()).expect("Can't find core::felt252.");
+    let params = as_var_id_vec(&["0", "1", "3", "5", "6"])
+        .into_iter()
+        .map(|id| Param { id, ty: felt252_ty.clone() })
+        .collect_vec();
+
+    let statements = add_store_statements_with_config(
+        &db,
+        statements,
+        &(|libfunc| LibfuncInfo { signature: get_lib_func_signature(&db, libfunc) }),
+        LocalVariables::default(),
+        &params,
+        StoreVariablesConfig { verify_invariants: true, ..Default::default() },
+    )
+    .unwrap()
+    .statements;
+    assert_eq!(
+        statements
+            .iter()
+            .map(|statement| replace_sierra_ids(&db, statement).to_string(&db))
+            .collect_vec(),
+        vec![
+            "felt252_add(0, 1) -> (2)",
+            "nope() -> ()",
+            "store_temp<felt252>(2) -> (2)",
+            "felt252_add(2, 3) -> (4)",
+            "nope() -> ()",
+            "store_temp<felt252>(4) -> (4)",
+            "felt252_add(5, 4) -> (5)",
+            "nope() -> ()",
+            "label_test::test::0:",
+            "store_temp<felt252>(5) -> (5)",
+            "felt252_add(5, 6) -> (6)",
+            "return()",
+        ]
+    );
+}
+
+/// Tests that a violation of the invariants of the state causes a panic when they are verified.
+#[test]
+#[should_panic(expected = "The known stack is inconsistent after statement 0: 0.")]
+fn store_variables_invariant_violation() {
+    let db = SierraGenDatabaseForTesting::default();
+    let felt252_ty =
+        db.get_concrete_type_id(db.core_felt252_ty()).expect("Can't find core::felt252.");
+    let deferred = VarState::Deferred {
+        info: DeferredVariableInfo { ty: felt252_ty, kind: DeferredVariableKind::Generic },
+    };
+    let mut handler = AddStoreVariableStatements::new(
+        &db,
+        LocalVariables::default(),
+        vec![("0".into(), deferred)],
+    );
+    // A deferred variable is not stored anywhere, so it may not be on the known stack.
+    handler.known_stack().push(&"0".into());
+    handler.config.verify_invariants = true;
+    super::handle_statements(
+        handler,
+        vec![dummy_simple_statement(&db, "nope", &[], &[])],
+        &(|libfunc| LibfuncInfo { signature: get_lib_func_signature(&db, libfunc) }),
+    );
+}
+
+/// Tests that the origins of the pushed values follow chains of renames and stores.
+#[test]
+fn push_values_origins() {
+    let db = SierraGenDatabaseForTesting::default();
+    let statements: Vec<pre_sierra::Statement> = vec![
+        dummy_simple_statement(&db, "function_call4", &[], &["0", "1", "2", "3"]),
+        dummy_push_values(&db, &[("2", "102"), ("3", "103"), ("0", "100")]),
+        dummy_push_values(&db, &[("102", "202")]),
+        dummy_return_statement(&["0"]),
+    ];
+
+    let StoreOutput { origins, .. } = add_store_statements_with_config(
+        &db,
+        statements,
+        &(|libfunc| LibfuncInfo { signature: get_lib_func_signature(&db, libfunc) }),
+        LocalVariables::default(),
+        &[],
+        StoreVariablesConfig { collect_origins: true, ..Default::default() },
+    )
+    .unwrap();
+    assert_eq!(
+        origins,
+        OrderedHashMap::from_iter(vec![
+            ("102".into(), "2".into()),
+            ("103".into(), "3".into()),
+            ("100".into(), "0".into()),
+            ("202".into(), "2".into()),
+        ])
+    );
+}
+
 /// Tests that the known stack is cleared after change to ap.
 #[test]
 fn push_values_clear_known_stack() {
@@ -826,3 +2579,14 @@ fn consecutive_appends_with_branch() {
         ]
     );
 }
+
+/// Tests that a branch to a label that never appears is reported by name.
+#[test]
+#[should_panic(expected = "Unhandled labels in 'store_variables': label_test::test::0 (1 branch).")]
+fn unhandled_label() {
+    let db = SierraGenDatabaseForTesting::default();
+    let statements: Vec<pre_sierra::Statement> =
+        vec![dummy_simple_branch(&db, "branch", &[], 0), dummy_return_statement(&[])];
+
+    test_add_store_statements(&db, statements, LocalVariables::default(), &[]);
+}
@@ -413,7 +413,7 @@ impl OutputVarInfo {
 /// for all the output variables in an output branch.
 ///
 /// See [OutputVarInfo].
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct BranchSignature {
     /// Information about the new variables created in the branch.
     pub vars: Vec<OutputVarInfo>,
@@ -459,6 +459,7 @@ pub trait ConcreteLibfunc {
 }
 
 /// Represents the signature of a library function.
+#[derive(Clone)]
 pub struct LibfuncSignature {
     /// The parameter types and other information for the parameters for calling a library
     /// function.
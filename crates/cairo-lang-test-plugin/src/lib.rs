@@ -1,8 +1,9 @@
-use anyhow::{Context, Result};
+use anyhow::{ensure, Context, Result};
 use cairo_felt::Felt252;
 use cairo_lang_compiler::db::RootDatabase;
 use cairo_lang_debug::DebugWithDb;
 use cairo_lang_defs::ids::{FreeFunctionId, FunctionWithBodyId, ModuleItemId};
+use cairo_lang_defs::plugin::PluginDiagnostic;
 use cairo_lang_diagnostics::ToOption;
 use cairo_lang_filesystem::ids::CrateId;
 use cairo_lang_lowering::ids::ConcreteFunctionWithBodyId;
@@ -24,17 +25,51 @@ use cairo_lang_utils::ordered_hash_map::{
 };
 use itertools::{chain, Itertools};
 use serde::{Deserialize, Serialize};
-pub use test_config::{try_extract_test_config, TestConfig};
+pub use test_config::{
+    recognized_test_attributes, test_config_lints, test_config_warnings, try_extract_test_config,
+    try_extract_test_config_lenient, try_extract_test_configs, ExtractedTestConfig,
+    PartialTestConfig, TestConfig, TestConfigExtractionOptions, TestDefinitionSpan,
+    DEFAULT_AVAILABLE_GAS,
+};
 
 pub mod plugin;
 pub mod test_config;
 pub use plugin::TestPlugin;
 
 const TEST_ATTR: &str = "test";
+const BENCH_ATTR: &str = "bench";
+const TEST_CASE_ATTR: &str = "test_case";
 const SHOULD_PANIC_ATTR: &str = "should_panic";
+const SHOULD_NOT_PANIC_ATTR: &str = "should_not_panic";
 const IGNORE_ATTR: &str = "ignore";
 const AVAILABLE_GAS_ATTR: &str = "available_gas";
+const NO_GAS_ATTR: &str = "no_gas";
+const TIMEOUT_ATTR: &str = "timeout";
+const FUZZER_ATTR: &str = "fuzzer";
+const EXPECTED_RESULT_ATTR: &str = "expected_result";
+const TEST_CFG_ATTR: &str = "test_cfg";
+const TEST_TAG_ATTR: &str = "test_tag";
+const GAS_REPORT_ATTR: &str = "gas_report";
+const TEST_NAME_ATTR: &str = "test_name";
+const MAX_GAS_ATTR: &str = "max_gas";
+const TEST_ISOLATED_ATTR: &str = "test_isolated";
+const EXPECTED_STEPS_ATTR: &str = "expected_steps";
+const TEST_AFTER_ATTR: &str = "test_after";
+const EXACT_GAS_ATTR: &str = "exact_gas";
 const STATIC_GAS_ARG: &str = "static";
+const GAS_ARG: &str = "gas";
+const MIN_GAS_ARG: &str = "min";
+const MAX_GAS_ARG: &str = "max";
+const MAX_STEPS_ARG: &str = "max";
+const EXPECTED_ARG: &str = "expected";
+const EXPECTED_CONTAINS_ARG: &str = "expected_contains";
+const EXPECTED_ANY_ARG: &str = "expected_any";
+const OUT_OF_GAS_ARG: &str = "out_of_gas";
+const EMPTY_PANIC_ARG: &str = "empty";
+const FUZZER_RUNS_ARG: &str = "runs";
+const FUZZER_SEED_ARG: &str = "seed";
+const FEATURE_ARG: &str = "feature";
+const REASON_ARG: &str = "reason";
 
 /// Runs Cairo compiler.
 ///
@@ -78,7 +113,7 @@ pub fn compile_test_prepared_db(
                 )
             })
             .collect();
-    let all_tests = find_all_tests(db, test_crate_ids.clone());
+    let (all_tests, warnings) = find_all_tests(db, test_crate_ids.clone());
     let sierra_program = db
         .get_sierra_program_for_functions(
             chain!(
@@ -92,29 +127,73 @@ pub fn compile_test_prepared_db(
         .to_option()
         .with_context(|| "Compilation failed without any diagnostics.")?;
     let replacer = DebugReplacer { db };
-    let sierra_program = replacer.apply(&sierra_program);
+    let mut sierra_program = replacer.apply(&sierra_program);
 
+    let mut entry_names = OrderedHashMap::<String, String>::default();
     let named_tests = all_tests
         .into_iter()
         .map(|(func_id, test)| {
-            (
-                format!(
-                    "{:?}",
-                    FunctionLongId {
-                        function: ConcreteFunction {
-                            generic_function: GenericFunctionId::Free(func_id),
-                            generic_args: vec![]
-                        }
+            let function_name = format!(
+                "{:?}",
+                FunctionLongId {
+                    function: ConcreteFunction {
+                        generic_function: GenericFunctionId::Free(func_id),
+                        generic_args: vec![]
                     }
-                    .debug(db)
-                ),
-                test,
-            )
+                }
+                .debug(db)
+            );
+            let base_name = if let Some(entry_name) = &test.entry_name {
+                entry_names.insert(function_name, entry_name.clone());
+                entry_name.clone()
+            } else {
+                function_name
+            };
+            let name = if test.args.is_empty() {
+                base_name
+            } else {
+                format!("{base_name}({})", test.args.iter().join(", "))
+            };
+            (name, test)
         })
         .collect_vec();
+    for (name, test) in &named_tests {
+        if test.entry_name.is_some() {
+            ensure!(
+                named_tests.iter().filter(|(other_name, _)| other_name == name).count() == 1,
+                "Multiple tests are named `{name}`."
+            );
+        }
+    }
+    // Tests with an entry name are found in the program by it, so their functions are renamed.
+    for func in &mut sierra_program.funcs {
+        if let Some(entry_name) =
+            func.id.debug_name.as_ref().and_then(|name| entry_names.get(name.as_str()))
+        {
+            func.id.debug_name = Some(entry_name.into());
+        }
+    }
+    // The renamed functions are found by name, so no other function of the program may share it.
+    for entry_name in entry_names.values() {
+        ensure!(
+            sierra_program
+                .funcs
+                .iter()
+                .filter(|func| func.id.debug_name.as_deref() == Some(entry_name.as_str()))
+                .count()
+                == 1,
+            "Multiple functions in the program are named `{entry_name}`."
+        );
+    }
     let contracts_info = get_contracts_info(db, main_crate_ids.clone(), &replacer)?;
 
-    Ok(TestCompilation { named_tests, sierra_program, function_set_costs, contracts_info })
+    Ok(TestCompilation {
+        named_tests,
+        sierra_program,
+        function_set_costs,
+        contracts_info,
+        warnings,
+    })
 }
 
 /// Compiled test cases.
@@ -132,30 +211,55 @@ pub struct TestCompilation {
     pub function_set_costs: OrderedHashMap<FunctionId, OrderedHashMap<CostTokenType, i32>>,
     pub named_tests: Vec<(String, TestConfig)>,
     pub sierra_program: Program,
+    /// The warnings of the test attributes, for the caller to report. Not serialized, as they
+    /// refer to the database the tests were compiled with.
+    #[serde(skip)]
+    pub warnings: Vec<PluginDiagnostic>,
 }
 
-/// Finds the tests in the requested crates.
+/// Returns the name of the function of the test with the given name, stripping the arguments of a
+/// test case instance.
+pub fn test_function_name(test_name: &str) -> &str {
+    test_name.split_once('(').map_or(test_name, |(function_name, _args)| function_name)
+}
+
+/// Finds the tests in the requested crates, together with the warnings of their attributes.
 fn find_all_tests(
     db: &dyn SemanticGroup,
     main_crates: Vec<CrateId>,
-) -> Vec<(FreeFunctionId, TestConfig)> {
+) -> (Vec<(FreeFunctionId, TestConfig)>, Vec<PluginDiagnostic>) {
     let mut tests = vec![];
+    let mut warnings = vec![];
     for crate_id in main_crates {
         let modules = db.crate_modules(crate_id);
         for module_id in modules.iter() {
             let Ok(module_items) = db.module_items(*module_id) else {
                 continue;
             };
-            tests.extend(module_items.iter().filter_map(|item| {
-                let ModuleItemId::FreeFunction(func_id) = item else { return None };
+            tests.extend(module_items.iter().flat_map(|item| {
+                let ModuleItemId::FreeFunction(func_id) = item else { return vec![] };
                 let Ok(attrs) =
                     db.function_with_body_attributes(FunctionWithBodyId::Free(*func_id))
                 else {
-                    return None;
+                    return vec![];
                 };
-                Some((*func_id, try_extract_test_config(db.upcast(), attrs).unwrap()?))
+                let function = func_id.stable_ptr(db.upcast()).lookup(db.upcast());
+                warnings.extend(test_config_warnings(&attrs));
+                try_extract_test_configs(
+                    db.upcast(),
+                    attrs,
+                    TestConfigExtractionOptions {
+                        active_cfgs: Some(&db.cfg_set()),
+                        function: Some(&function),
+                        ..Default::default()
+                    },
+                )
+                .unwrap()
+                .into_iter()
+                .map(|test| (*func_id, test))
+                .collect_vec()
             }));
         }
     }
-    tests
+    (tests, warnings)
 }
@@ -3,8 +3,9 @@ use cairo_lang_syntax::attribute::structured::AttributeListStructurize;
 use cairo_lang_syntax::node::ast;
 use cairo_lang_syntax::node::db::SyntaxGroup;
 
-use super::{AVAILABLE_GAS_ATTR, IGNORE_ATTR, SHOULD_PANIC_ATTR, TEST_ATTR};
-use crate::test_config::try_extract_test_config;
+use crate::test_config::{
+    recognized_test_attributes, try_extract_test_config, TestConfigExtractionOptions,
+};
 
 /// Plugin to create diagnostics for tests attributes.
 #[derive(Debug, Default)]
@@ -16,7 +17,15 @@ impl MacroPlugin for TestPlugin {
         PluginResult {
             code: None,
             diagnostics: if let ast::Item::FreeFunction(free_func_ast) = item_ast {
-                try_extract_test_config(db, free_func_ast.attributes(db).structurize(db)).err()
+                try_extract_test_config(
+                    db,
+                    free_func_ast.attributes(db).structurize(db),
+                    TestConfigExtractionOptions {
+                        function: Some(&free_func_ast),
+                        ..Default::default()
+                    },
+                )
+                .err()
             } else {
                 None
             }
@@ -26,11 +35,6 @@ impl MacroPlugin for TestPlugin {
     }
 
     fn declared_attributes(&self) -> Vec<String> {
-        vec![
-            TEST_ATTR.to_string(),
-            AVAILABLE_GAS_ATTR.to_string(),
-            SHOULD_PANIC_ATTR.to_string(),
-            IGNORE_ATTR.to_string(),
-        ]
+        recognized_test_attributes().iter().map(|attr| attr.to_string()).collect()
     }
 }
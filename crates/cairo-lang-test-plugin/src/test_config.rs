@@ -1,186 +1,2297 @@
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+use anyhow::{bail, ensure, Context};
 use cairo_felt::Felt252;
 use cairo_lang_defs::plugin::PluginDiagnostic;
+use cairo_lang_filesystem::cfg::{Cfg, CfgSet};
 use cairo_lang_syntax::attribute::structured::{Attribute, AttributeArg, AttributeArgVariant};
 use cairo_lang_syntax::node::db::SyntaxGroup;
-use cairo_lang_syntax::node::{ast, TypedSyntaxNode};
+use cairo_lang_syntax::node::helpers::GetIdentifier;
+use cairo_lang_syntax::node::ids::SyntaxStablePtrId;
+use cairo_lang_syntax::node::kind::SyntaxKind;
+use cairo_lang_syntax::node::{ast, Terminal, TypedSyntaxNode};
 use cairo_lang_utils::OptionHelper;
+use itertools::{chain, Itertools};
 use num_traits::ToPrimitive;
 use serde::{Deserialize, Serialize};
 
-use super::{AVAILABLE_GAS_ATTR, IGNORE_ATTR, SHOULD_PANIC_ATTR, STATIC_GAS_ARG, TEST_ATTR};
+use super::{
+    AVAILABLE_GAS_ATTR, BENCH_ATTR, EMPTY_PANIC_ARG, EXACT_GAS_ATTR, EXPECTED_ANY_ARG,
+    EXPECTED_ARG, EXPECTED_CONTAINS_ARG, EXPECTED_RESULT_ATTR, EXPECTED_STEPS_ATTR, FEATURE_ARG,
+    FUZZER_ATTR, FUZZER_RUNS_ARG, FUZZER_SEED_ARG, GAS_ARG, GAS_REPORT_ATTR, IGNORE_ATTR,
+    MAX_GAS_ARG, MAX_GAS_ATTR, MAX_STEPS_ARG, MIN_GAS_ARG, NO_GAS_ATTR, OUT_OF_GAS_ARG, REASON_ARG,
+    SHOULD_NOT_PANIC_ATTR, SHOULD_PANIC_ATTR, STATIC_GAS_ARG, TEST_AFTER_ATTR, TEST_ATTR,
+    TEST_CASE_ATTR, TEST_CFG_ATTR, TEST_ISOLATED_ATTR, TEST_NAME_ATTR, TEST_TAG_ATTR, TIMEOUT_ATTR,
+};
+
+#[cfg(test)]
+#[path = "test_config_test.rs"]
+mod test;
+
+/// The magic value that prefixes the panic data of a panic with a `ByteArray` message.
+const BYTE_ARRAY_MAGIC: &str = "46a6158a16a947e5916b2a2ca68501a45e93d7110e81aa2d6438b1c57c879a3";
+/// The number of bytes in a full word of a `ByteArray`.
+const BYTES_IN_BYTES31: usize = 31;
+/// The panic message of a run that ran out of gas.
+pub const OUT_OF_GAS_PANIC_MESSAGE: &str = "Out of gas";
+/// The gas available to a test that does not specify it. A reasonably large gas, such that
+/// infinite loops will run out of gas.
+pub const DEFAULT_AVAILABLE_GAS: usize = u32::MAX as usize;
+/// The name of the inline macro creating an array, accepted as the expected panic values.
+const ARRAY_MACRO: &str = "array";
+/// The description of an expected panic value in diagnostics.
+const PANIC_VALUE_DESCRIPTION: &str = "Expected panic value";
+/// The description of a test case argument in diagnostics.
+const TEST_CASE_ARG_DESCRIPTION: &str = "Test case argument";
+
+/// The version of the serialization format of [TestConfig].
+/// Should be bumped whenever the fields of [TestConfig] change.
+pub const TEST_CONFIG_VERSION: u32 = 5;
 
 /// Expectation for a panic case.
-#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Hash)]
 pub enum PanicExpectation {
     /// Accept any panic value.
     Any,
     /// Accept only this specific vector of panics.
+    /// Expected string messages are stored in their `ByteArray` panic encoding.
     Exact(Vec<Felt252>),
+    /// Accept only panics containing this vector as a contiguous subsequence.
+    Contains(Vec<Felt252>),
+    /// Accept only a panic due to running out of gas.
+    OutOfGas,
+    /// Accept only panics equal to one of these vectors.
+    /// Expected string messages are stored in their `ByteArray` panic encoding.
+    AnyOf(Vec<Vec<Felt252>>),
+}
+
+impl PanicExpectation {
+    /// Returns whether the panic data of a panicking run matches the expectation.
+    pub fn matches(&self, actual: &[Felt252]) -> bool {
+        match self {
+            PanicExpectation::Any => true,
+            PanicExpectation::Exact(expected) => actual == expected.as_slice(),
+            PanicExpectation::Contains(expected) => {
+                expected.is_empty()
+                    || actual.windows(expected.len()).any(|window| window == expected.as_slice())
+            }
+            PanicExpectation::OutOfGas => {
+                actual == [Felt252::from_bytes_be(OUT_OF_GAS_PANIC_MESSAGE.as_bytes())]
+            }
+            PanicExpectation::AnyOf(options) => {
+                options.iter().any(|expected| actual == expected.as_slice())
+            }
+        }
+    }
+}
+impl fmt::Display for PanicExpectation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PanicExpectation::Any => write!(f, "any"),
+            PanicExpectation::Exact(expected) => write!(f, "[{}]", expected.iter().join(", ")),
+            PanicExpectation::Contains(expected) => {
+                write!(f, "containing [{}]", expected.iter().join(", "))
+            }
+            PanicExpectation::OutOfGas => write!(f, "out of gas"),
+            PanicExpectation::AnyOf(options) => write!(
+                f,
+                "any of [{}]",
+                options
+                    .iter()
+                    .map(|expected| format!("[{}]", expected.iter().join(", ")))
+                    .join(", ")
+            ),
+        }
+    }
 }
 
 /// Expectation for a result of a test.
-#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Hash)]
 pub enum TestExpectation {
     /// Running the test should not panic.
     Success,
     /// Running the test should result in a panic.
     Panics(PanicExpectation),
+    /// Running the test should not panic, and return exactly these values.
+    ReturnsValue(Vec<Felt252>),
+}
+
+/// The kind of a test.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Hash)]
+pub enum TestKind {
+    /// A correctness test, marked with `#[test]`.
+    Test,
+    /// A benchmark, marked with `#[bench]`.
+    Bench,
+}
+
+impl TestExpectation {
+    /// Returns whether the result of a run matches the expectation, where `values` are the panic
+    /// data if the run `panicked`, and the returned values otherwise.
+    pub fn matches_result(&self, panicked: bool, values: &[Felt252]) -> bool {
+        match self {
+            TestExpectation::Success => !panicked,
+            TestExpectation::ReturnsValue(expected) => !panicked && values == expected.as_slice(),
+            TestExpectation::Panics(panic_expectation) => {
+                panicked && panic_expectation.matches(values)
+            }
+        }
+    }
+}
+impl fmt::Display for TestExpectation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TestExpectation::Success => write!(f, "expects success"),
+            TestExpectation::Panics(PanicExpectation::Any) => write!(f, "expects panic (any)"),
+            TestExpectation::Panics(panic_expectation) => {
+                write!(f, "expects panic: {panic_expectation}")
+            }
+            TestExpectation::ReturnsValue(expected) => {
+                write!(f, "expects result: [{}]", expected.iter().join(", "))
+            }
+        }
+    }
+}
+
+/// The gas metering of a test.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq, Hash)]
+pub enum GasMode {
+    /// The test runs with this amount of gas. Set by `#[available_gas(<number>)]`, or to the
+    /// default gas if no gas attribute is given.
+    /// Zero gas is allowed, for asserting that the test runs out of gas immediately.
+    Limited(usize),
+    /// The test runs without a gas supply, and its gas usage is the statically computed cost.
+    /// Set by `#[available_gas(static)]`.
+    Static,
+    /// The test runs as with [GasMode::Static], but its gas usage is not reported. Set by
+    /// `#[no_gas]`. Gas metering cannot be turned off for a single test, as the gas of all the
+    /// tests is computed together with the program.
+    Unreported,
 }
 
 /// The configuration for running a single test.
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
 pub struct TestConfig {
-    /// The amount of gas the test requested.
-    pub available_gas: Option<usize>,
+    /// The version of the serialization format the configuration was created with.
+    /// Configurations serialized before the format was versioned have version 0.
+    #[serde(default)]
+    pub version: u32,
+    /// The kind of the test.
+    pub kind: TestKind,
+    /// The gas metering of the test.
+    pub gas_mode: GasMode,
+    /// The minimal amount of gas the test is expected to consume, if bounded.
+    pub min_gas: Option<usize>,
+    /// The maximal amount of gas the test is expected to consume, if bounded.
+    pub max_gas: Option<usize>,
     /// The expected result of the run.
     pub expectation: TestExpectation,
     /// Should the test be ignored.
     pub ignored: bool,
+    /// The reason the test is ignored, if given.
+    pub ignore_reason: Option<String>,
+    /// The arguments the test function is called with. Only set for instances of `#[test_case]`.
+    pub args: Vec<Felt252>,
+    /// The wall-clock time limit for running the test, in milliseconds.
+    pub timeout_ms: Option<u64>,
+    /// The configuration of the fuzzer generating the arguments of the test, if fuzzed.
+    pub fuzzer: Option<FuzzerConfig>,
+    /// The feature that must be active for the test to be collected, if any. Set by
+    /// `#[test_cfg(feature: "<name>")]`.
+    pub required_cfg: Option<String>,
+    /// The location of the name of the test function, if the function was given on extraction.
+    #[serde(default)]
+    pub definition_span: Option<TestDefinitionSpan>,
+    /// The labels of the test, set by `#[test_tag("<tag>")]`, in the order of their attributes.
+    /// Repeated tags are kept once, at their first occurrence.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// The gas the test is expected to consume, set by `#[gas_report(expected: <number>)]`. Only
+    /// used for reporting the difference from the actual gas usage, unlike the enforced gas
+    /// limits.
+    #[serde(default)]
+    pub expected_gas_report: Option<usize>,
+    /// The name of the test, replacing the name of its function, set by
+    /// `#[test_name("<name>")]`.
+    #[serde(default)]
+    pub entry_name: Option<String>,
+    /// The gas the test may consume without failing, set by `#[max_gas(<number>)]`. Unlike the
+    /// available gas, running past it does not stop the run, but fails the test after it.
+    #[serde(default)]
+    pub gas_budget: Option<usize>,
+    /// Whether the test must run in isolation from other tests, e.g., as it touches global state,
+    /// set by `#[test_isolated]`. Scheduling such tests is up to the runner.
+    #[serde(default)]
+    pub isolated: bool,
+    /// The maximal number of VM steps the test is expected to run, set by
+    /// `#[expected_steps(max: <number>)]`. Unlike the gas limits, it does not depend on the gas
+    /// costs of the libfuncs.
+    #[serde(default)]
+    pub max_steps: Option<usize>,
+    /// The names of the tests this test should run after, set by `#[test_after("<name>")]`, in
+    /// the order of their attributes. Repeated names are kept once, at their first occurrence.
+    /// Only a scheduling hint for the runner: the names are not resolved, and cycles are not
+    /// detected by the plugin.
+    #[serde(default)]
+    pub run_after: Vec<String>,
+    /// The exact amount of gas the test is expected to consume, set by `#[exact_gas(<number>)]`.
+    /// The test runs with the default gas, and the runner fails it if its consumption differs.
+    #[serde(default)]
+    pub exact_gas: Option<usize>,
+    /// The reason the test is expected to panic, set by `#[should_panic(reason: "<reason>")]`. Only
+    /// used for reporting, and does not affect the matching of the panic.
+    #[serde(default)]
+    pub panic_reason: Option<String>,
+}
+
+/// The 64-bit FNV-1a hasher of [TestConfig::config_fingerprint].
+/// Unlike [std::collections::hash_map::DefaultHasher], whose algorithm may change between Rust
+/// releases, the algorithm is fixed. Integers are hashed as little-endian bytes, and `usize` and
+/// `isize` as 64-bit integers, so the hash does not depend on the platform either.
+struct FingerprintHasher(u64);
+
+impl FingerprintHasher {
+    const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+    const PRIME: u64 = 0x100000001b3;
+}
+
+impl Default for FingerprintHasher {
+    fn default() -> Self {
+        Self(Self::OFFSET_BASIS)
+    }
+}
+
+impl Hasher for FingerprintHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ u64::from(*byte)).wrapping_mul(Self::PRIME);
+        }
+    }
+
+    fn write_u16(&mut self, i: u16) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u128(&mut self, i: u128) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64);
+    }
+
+    fn write_i16(&mut self, i: i16) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_i32(&mut self, i: i32) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_i64(&mut self, i: i64) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_i128(&mut self, i: i128) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_isize(&mut self, i: isize) {
+        self.write_i64(i as i64);
+    }
+}
+
+/// Hashes the fields that affect the run of the test, in this order: kind, gas mode, gas bounds,
+/// expectation, ignored, ignore reason, args, timeout, fuzzer and required feature, followed by
+/// the fields added later, each of which is only hashed if it is not empty: tags, expected gas
+/// report, entry name, gas budget, isolation, maximal steps, tests to run after, exact gas and
+/// panic reason.
+/// The version and the definition span are not hashed, as they do not affect the run.
+/// Fields added in the future are appended in the same manner, so the hash of a configuration
+/// that does not use them does not change.
+impl Hash for TestConfig {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.kind.hash(state);
+        self.gas_mode.hash(state);
+        self.min_gas.hash(state);
+        self.max_gas.hash(state);
+        self.expectation.hash(state);
+        self.ignored.hash(state);
+        self.ignore_reason.hash(state);
+        self.args.hash(state);
+        self.timeout_ms.hash(state);
+        self.fuzzer.hash(state);
+        self.required_cfg.hash(state);
+        if !self.tags.is_empty() {
+            self.tags.hash(state);
+        }
+        if let Some(expected_gas_report) = self.expected_gas_report {
+            expected_gas_report.hash(state);
+        }
+        if let Some(entry_name) = &self.entry_name {
+            entry_name.hash(state);
+        }
+        if let Some(gas_budget) = self.gas_budget {
+            gas_budget.hash(state);
+        }
+        if self.isolated {
+            self.isolated.hash(state);
+        }
+        if let Some(max_steps) = self.max_steps {
+            max_steps.hash(state);
+        }
+        if !self.run_after.is_empty() {
+            self.run_after.hash(state);
+        }
+        if let Some(exact_gas) = self.exact_gas {
+            exact_gas.hash(state);
+        }
+        if let Some(panic_reason) = &self.panic_reason {
+            panic_reason.hash(state);
+        }
+    }
+}
+
+/// The location of the definition of a test, at the name of its function.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct TestDefinitionSpan {
+    /// The path of the file of the test.
+    pub file: String,
+    /// The line of the function name, 0 based.
+    pub line: usize,
+    /// The character of the function name inside its line, 0 based.
+    pub col: usize,
+}
+
+impl TestDefinitionSpan {
+    /// Returns the location of the name of the given function.
+    pub fn from_function(db: &dyn SyntaxGroup, function: &ast::FunctionWithBody) -> Option<Self> {
+        let name = function.declaration(db).name(db);
+        let file_id = name.stable_ptr().untyped().file_id(db);
+        let position = name
+            .as_syntax_node()
+            .span_start_without_trivia(db)
+            .position_in_file(db.upcast(), file_id)?;
+        Some(Self { file: file_id.full_path(db.upcast()), line: position.line, col: position.col })
+    }
+}
+
+/// A partial configuration of a test, overriding the fields of a [TestConfig] that are set.
+/// Fields that are themselves optional in [TestConfig] are overridden by `Some(None)`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PartialTestConfig {
+    /// Overrides [TestConfig::kind].
+    pub kind: Option<TestKind>,
+    /// Overrides [TestConfig::gas_mode].
+    pub gas_mode: Option<GasMode>,
+    /// Overrides [TestConfig::min_gas].
+    pub min_gas: Option<Option<usize>>,
+    /// Overrides [TestConfig::max_gas].
+    pub max_gas: Option<Option<usize>>,
+    /// Overrides [TestConfig::expectation].
+    pub expectation: Option<TestExpectation>,
+    /// Overrides [TestConfig::ignored].
+    pub ignored: Option<bool>,
+    /// Overrides [TestConfig::ignore_reason].
+    pub ignore_reason: Option<Option<String>>,
+    /// Overrides [TestConfig::args].
+    pub args: Option<Vec<Felt252>>,
+    /// Overrides [TestConfig::timeout_ms].
+    pub timeout_ms: Option<Option<u64>>,
+    /// Overrides [TestConfig::fuzzer].
+    pub fuzzer: Option<Option<FuzzerConfig>>,
+    /// Overrides [TestConfig::required_cfg].
+    pub required_cfg: Option<Option<String>>,
+    /// Overrides [TestConfig::definition_span].
+    pub definition_span: Option<Option<TestDefinitionSpan>>,
+    /// Overrides [TestConfig::tags].
+    pub tags: Option<Vec<String>>,
+    /// Overrides [TestConfig::expected_gas_report].
+    pub expected_gas_report: Option<Option<usize>>,
+    /// Overrides [TestConfig::entry_name].
+    pub entry_name: Option<Option<String>>,
+    /// Overrides [TestConfig::gas_budget].
+    pub gas_budget: Option<Option<usize>>,
+    /// Overrides [TestConfig::isolated].
+    pub isolated: Option<bool>,
+    /// Overrides [TestConfig::max_steps].
+    pub max_steps: Option<Option<usize>>,
+    /// Overrides [TestConfig::run_after].
+    pub run_after: Option<Vec<String>>,
+    /// Overrides [TestConfig::exact_gas].
+    pub exact_gas: Option<Option<usize>>,
+    /// Overrides [TestConfig::panic_reason].
+    pub panic_reason: Option<Option<String>>,
+}
+
+/// A flat description of a test, for runners outside of Rust that read tests as JSON.
+/// Unlike [TestConfig], its format does not change with the internal representation of the
+/// configuration. Felt252 values are given as decimal strings, to avoid precision loss.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct TestDescriptor {
+    /// The gas the test runs with, or `None` if it runs without a gas supply.
+    pub available_gas: Option<usize>,
+    /// Whether the test is expected to panic.
+    pub should_panic: bool,
+    /// The exact panic data the test is expected to panic with, if the test should panic with
+    /// specific data. `None` for a test that should panic with any data, or whose expected data is
+    /// not exact, as with `expected_contains` and `expected_any`.
+    pub expected_panic: Option<Vec<String>>,
+    /// Whether the test is ignored.
+    pub ignored: bool,
+}
+
+impl From<&TestConfig> for TestDescriptor {
+    fn from(config: &TestConfig) -> Self {
+        let to_decimal_strings =
+            |values: &[Felt252]| values.iter().map(|value| value.to_string()).collect_vec();
+        let (should_panic, expected_panic) = match &config.expectation {
+            TestExpectation::Panics(PanicExpectation::Exact(expected)) => {
+                (true, Some(to_decimal_strings(expected)))
+            }
+            TestExpectation::Panics(PanicExpectation::OutOfGas) => (
+                true,
+                Some(to_decimal_strings(&[Felt252::from_bytes_be(
+                    OUT_OF_GAS_PANIC_MESSAGE.as_bytes(),
+                )])),
+            ),
+            TestExpectation::Panics(_) => (true, None),
+            TestExpectation::Success | TestExpectation::ReturnsValue(_) => (false, None),
+        };
+        Self {
+            available_gas: config.available_gas(),
+            should_panic,
+            expected_panic,
+            ignored: config.ignored,
+        }
+    }
+}
+
+/// The configuration of the fuzzer of a test.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Hash)]
+pub struct FuzzerConfig {
+    /// The number of runs of the test, each with different generated arguments.
+    pub runs: u64,
+    /// The seed for generating the arguments, if fixed.
+    pub seed: Option<u64>,
+}
+
+impl TestConfig {
+    /// Returns the amount of gas the test runs with, or `None` if it runs without a gas supply.
+    pub fn available_gas(&self) -> Option<usize> {
+        match self.gas_mode {
+            GasMode::Limited(available_gas) => Some(available_gas),
+            GasMode::Static | GasMode::Unreported => None,
+        }
+    }
+
+    /// Returns a fingerprint of the configuration, identifying it for deciding whether to rerun the
+    /// test. The fingerprint is computed with the fixed FNV-1a algorithm, so it is the same across
+    /// runs, builds and platforms. See the [Hash] implementation for the hashed fields.
+    pub fn config_fingerprint(&self) -> u64 {
+        let mut hasher = FingerprintHasher::default();
+        self.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Returns a builder of a test configuration, with the defaults of a test without any
+    /// attribute other than `#[test]`.
+    pub fn builder() -> TestConfigBuilder {
+        TestConfigBuilder::new()
+    }
+
+    /// Returns the configuration of a test without any attribute other than `#[test]`, with the
+    /// fields set in `fields` replacing the defaults, or an error if the configuration is
+    /// contradictory. See [TestConfig::validate] for the checked invariants.
+    pub fn try_new(fields: PartialTestConfig) -> anyhow::Result<Self> {
+        let config = Self::builder().build().overlay(fields);
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Checks the invariants that [try_extract_test_config] enforces on configurations extracted
+    /// from attributes:
+    /// * The minimal gas is not greater than the maximal gas.
+    /// * Gas bounds are only set for a test running with limited gas, and a maximal gas is the
+    ///   gas the test runs with.
+    /// * A benchmark does not expect a panic.
+    /// * An ignore reason is only set for an ignored test.
+    /// * A panic reason is only set for a test that expects a panic.
+    /// * The timeout, if set, is positive.
+    /// * The required feature, if set, is not empty.
+    /// * The tags are not empty, and are not repeated.
+    /// * The name of the test, if set, is not empty.
+    /// * The gas budget, if set, does not exceed the gas the test runs with.
+    /// * The maximal number of steps, if set, is positive.
+    /// * The names of the tests to run after are not empty, and are not repeated.
+    /// * The exact gas, if set, is not combined with gas bounds or a gas budget, and does not
+    ///   exceed the gas the test runs with, which must be limited.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if let (Some(min_gas), Some(max_gas)) = (self.min_gas, self.max_gas) {
+            ensure!(
+                min_gas <= max_gas,
+                "Minimal gas {min_gas} is greater than maximal gas {max_gas}."
+            );
+        }
+        if self.min_gas.is_some() || self.max_gas.is_some() {
+            let GasMode::Limited(available_gas) = self.gas_mode else {
+                bail!("Gas bounds require the test to run with limited gas.");
+            };
+            if let Some(max_gas) = self.max_gas {
+                ensure!(
+                    available_gas == max_gas,
+                    "The test runs with {available_gas} gas, instead of its maximal gas {max_gas}."
+                );
+            }
+        }
+        ensure!(
+            !(self.kind == TestKind::Bench
+                && matches!(self.expectation, TestExpectation::Panics(_))),
+            "A benchmark cannot expect a panic."
+        );
+        ensure!(
+            self.ignored || self.ignore_reason.is_none(),
+            "An ignore reason is set for a test that is not ignored."
+        );
+        ensure!(
+            matches!(self.expectation, TestExpectation::Panics(_)) || self.panic_reason.is_none(),
+            "A panic reason is set for a test that does not expect a panic."
+        );
+        ensure!(self.timeout_ms != Some(0), "The timeout must be positive.");
+        ensure!(
+            self.required_cfg.as_ref().map_or(true, |feature| !feature.is_empty()),
+            "The required feature must not be empty."
+        );
+        ensure!(self.tags.iter().all(|tag| !tag.is_empty()), "Tags must not be empty.");
+        ensure!(self.tags.iter().all_unique(), "Tags must not be repeated.");
+        ensure!(
+            self.entry_name.as_ref().map_or(true, |name| !name.is_empty()),
+            "The name of the test must not be empty."
+        );
+        if let (Some(gas_budget), Some(available_gas)) = (self.gas_budget, self.available_gas()) {
+            ensure!(
+                gas_budget <= available_gas,
+                "Gas budget {gas_budget} is greater than the available gas {available_gas}."
+            );
+        }
+        ensure!(self.max_steps != Some(0), "The maximal number of steps must be positive.");
+        ensure!(
+            self.run_after.iter().all(|name| !name.is_empty()),
+            "The names of the tests to run after must not be empty."
+        );
+        ensure!(
+            self.run_after.iter().all_unique(),
+            "The names of the tests to run after must not be repeated."
+        );
+        if let Some(exact_gas) = self.exact_gas {
+            ensure!(
+                self.min_gas.is_none() && self.max_gas.is_none() && self.gas_budget.is_none(),
+                "Exact gas cannot be combined with gas bounds or a gas budget."
+            );
+            let GasMode::Limited(available_gas) = self.gas_mode else {
+                bail!("Exact gas requires the test to run with limited gas.");
+            };
+            ensure!(
+                exact_gas <= available_gas,
+                "Exact gas {exact_gas} is greater than the available gas {available_gas}."
+            );
+        }
+        Ok(())
+    }
+
+    /// Returns the configuration with the fields set in `override_config` replacing these of
+    /// `self`, and the other fields inherited from `self`.
+    pub fn overlay(self, override_config: PartialTestConfig) -> TestConfig {
+        TestConfig {
+            version: self.version,
+            kind: override_config.kind.unwrap_or(self.kind),
+            gas_mode: override_config.gas_mode.unwrap_or(self.gas_mode),
+            min_gas: override_config.min_gas.unwrap_or(self.min_gas),
+            max_gas: override_config.max_gas.unwrap_or(self.max_gas),
+            expectation: override_config.expectation.unwrap_or(self.expectation),
+            ignored: override_config.ignored.unwrap_or(self.ignored),
+            ignore_reason: override_config.ignore_reason.unwrap_or(self.ignore_reason),
+            args: override_config.args.unwrap_or(self.args),
+            timeout_ms: override_config.timeout_ms.unwrap_or(self.timeout_ms),
+            fuzzer: override_config.fuzzer.unwrap_or(self.fuzzer),
+            required_cfg: override_config.required_cfg.unwrap_or(self.required_cfg),
+            definition_span: override_config.definition_span.unwrap_or(self.definition_span),
+            tags: override_config.tags.unwrap_or(self.tags),
+            expected_gas_report: override_config
+                .expected_gas_report
+                .unwrap_or(self.expected_gas_report),
+            entry_name: override_config.entry_name.unwrap_or(self.entry_name),
+            gas_budget: override_config.gas_budget.unwrap_or(self.gas_budget),
+            isolated: override_config.isolated.unwrap_or(self.isolated),
+            max_steps: override_config.max_steps.unwrap_or(self.max_steps),
+            run_after: override_config.run_after.unwrap_or(self.run_after),
+            exact_gas: override_config.exact_gas.unwrap_or(self.exact_gas),
+            panic_reason: override_config.panic_reason.unwrap_or(self.panic_reason),
+        }
+    }
+
+    /// Deserializes a configuration from its JSON serialization.
+    /// Fails with a descriptive error if the configuration was serialized with a different version
+    /// of the format.
+    pub fn from_serialized(serialized: &str) -> anyhow::Result<Self> {
+        /// The version of a serialized configuration, deserialized ahead of its other fields.
+        #[derive(Deserialize)]
+        struct VersionTag {
+            #[serde(default)]
+            version: u32,
+        }
+        let VersionTag { version } = serde_json::from_str(serialized)
+            .with_context(|| "Failed to read the version of the test config.")?;
+        ensure!(
+            version == TEST_CONFIG_VERSION,
+            "Test config of version {version} is not supported, expected version \
+             {TEST_CONFIG_VERSION}. Recompile the tests to update it."
+        );
+        serde_json::from_str(serialized).with_context(|| "Failed to deserialize the test config.")
+    }
+}
+
+/// A builder of a [TestConfig].
+#[derive(Clone, Debug)]
+pub struct TestConfigBuilder {
+    config: TestConfig,
+}
+
+impl TestConfigBuilder {
+    fn new() -> Self {
+        Self {
+            config: TestConfig {
+                version: TEST_CONFIG_VERSION,
+                kind: TestKind::Test,
+                gas_mode: GasMode::Limited(DEFAULT_AVAILABLE_GAS),
+                min_gas: None,
+                max_gas: None,
+                expectation: TestExpectation::Success,
+                ignored: false,
+                ignore_reason: None,
+                args: vec![],
+                timeout_ms: None,
+                fuzzer: None,
+                required_cfg: None,
+                definition_span: None,
+                tags: vec![],
+                expected_gas_report: None,
+                entry_name: None,
+                gas_budget: None,
+                isolated: false,
+                max_steps: None,
+                run_after: vec![],
+                exact_gas: None,
+                panic_reason: None,
+            },
+        }
+    }
+
+    /// Sets the gas available to the test, where `None` runs the test without gas.
+    pub fn available_gas(&mut self, available_gas: Option<usize>) -> &mut Self {
+        self.config.gas_mode = available_gas.map_or(GasMode::Static, GasMode::Limited);
+        self
+    }
+
+    /// Sets the gas metering of the test.
+    pub fn gas_mode(&mut self, gas_mode: GasMode) -> &mut Self {
+        self.config.gas_mode = gas_mode;
+        self
+    }
+
+    /// Sets the test to be expected to panic, as described by `panic_expectation`.
+    pub fn expect_panic(&mut self, panic_expectation: PanicExpectation) -> &mut Self {
+        self.config.expectation = TestExpectation::Panics(panic_expectation);
+        self
+    }
+
+    /// Sets the test to be expected to succeed. This is the default.
+    pub fn expect_success(&mut self) -> &mut Self {
+        self.config.expectation = TestExpectation::Success;
+        self
+    }
+
+    /// Sets whether the test is ignored.
+    pub fn ignored(&mut self, ignored: bool) -> &mut Self {
+        self.config.ignored = ignored;
+        self
+    }
+
+    /// Returns the configuration built so far. The builder may be reused afterwards.
+    pub fn build(&mut self) -> TestConfig {
+        self.config.clone()
+    }
+}
+
+/// The result of a successful extraction of a test configuration from attributes.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ExtractedTestConfig {
+    /// The attributes do not mark a test.
+    NotATest,
+    /// The attributes mark a test with this configuration.
+    Test(TestConfig),
+    /// The attributes mark a test that requires a feature that is not active.
+    Inactive,
+}
+
+/// Returns the names of all the attributes recognized by the test plugin.
+pub fn recognized_test_attributes() -> &'static [&'static str] {
+    &[
+        TEST_ATTR,
+        BENCH_ATTR,
+        TEST_CASE_ATTR,
+        AVAILABLE_GAS_ATTR,
+        NO_GAS_ATTR,
+        SHOULD_PANIC_ATTR,
+        SHOULD_NOT_PANIC_ATTR,
+        IGNORE_ATTR,
+        TIMEOUT_ATTR,
+        FUZZER_ATTR,
+        EXPECTED_RESULT_ATTR,
+        TEST_CFG_ATTR,
+        TEST_TAG_ATTR,
+        GAS_REPORT_ATTR,
+        TEST_NAME_ATTR,
+        MAX_GAS_ATTR,
+        TEST_ISOLATED_ATTR,
+        EXPECTED_STEPS_ATTR,
+        TEST_AFTER_ATTR,
+        EXACT_GAS_ATTR,
+    ]
+}
+
+/// The options of the extraction of a test configuration from attributes.
+#[derive(Clone, Copy)]
+pub struct TestConfigExtractionOptions<'a> {
+    /// Whether attributes that should only appear on tests are diagnosed without `#[test]`.
+    /// Otherwise, they are accepted, and the test they configure is returned, as `#[test]` may
+    /// still be added by a later expansion.
+    pub strict: bool,
+    /// The active features, if known. A test whose feature required by
+    /// `#[test_cfg(feature: "<name>")]` is not in it is [ExtractedTestConfig::Inactive].
+    /// Otherwise, the required feature is only recorded in the configuration.
+    pub active_cfgs: Option<&'a CfgSet>,
+    /// The function of the test, if known. The location of its name is recorded as the definition
+    /// of the test.
+    pub function: Option<&'a ast::FunctionWithBody>,
+    /// The gas a test without a gas attribute runs with, or None to run it without a gas supply.
+    pub default_gas: Option<usize>,
+}
+impl Default for TestConfigExtractionOptions<'_> {
+    /// Strict extraction with the default gas of the compiler, [DEFAULT_AVAILABLE_GAS].
+    fn default() -> Self {
+        Self {
+            strict: true,
+            active_cfgs: None,
+            function: None,
+            default_gas: Some(DEFAULT_AVAILABLE_GAS),
+        }
+    }
 }
 
 /// Extracts the configuration of a tests from attributes, or returns the diagnostics if the
-/// attributes are set illegally.
+/// attributes are set illegally. See [TestConfigExtractionOptions] for the effect of `options`.
 pub fn try_extract_test_config(
     db: &dyn SyntaxGroup,
     attrs: Vec<Attribute>,
-) -> Result<Option<TestConfig>, Vec<PluginDiagnostic>> {
+    options: TestConfigExtractionOptions<'_>,
+) -> Result<ExtractedTestConfig, Vec<PluginDiagnostic>> {
+    let (config, fatal_diagnostics, recoverable_diagnostics) =
+        extract_test_config(db, &attrs, options);
+    if fatal_diagnostics.is_empty() && recoverable_diagnostics.is_empty() {
+        Ok(config)
+    } else {
+        Err(chain!(fatal_diagnostics, recoverable_diagnostics).collect())
+    }
+}
+
+/// Extracts the configuration of a test from attributes, as [try_extract_test_config], but also
+/// returns a best-effort configuration if the attributes only have recoverable diagnostics.
+/// Returns the configuration of the test, if the attributes define an active test and have no
+/// fatal diagnostics, together with all the diagnostics of the attributes.
+///
+/// Recoverable diagnostics are of attributes whose settings fall back to their defaults:
+/// * Duplicate attributes, of which only the first is used.
+/// * Arguments of `#[test]`, `#[bench]`, `#[no_gas]`, `#[should_not_panic]` and `#[test_isolated]`,
+///   which are ignored.
+/// * Malformed gas attributes, and `#[no_gas]` together with `#[available_gas]`, where the test
+///   runs with `default_gas`, or with the gas of `#[available_gas]` if it is well-formed.
+/// * Malformed ignore reasons, timeouts, fuzzer configurations, test case arguments, tags, gas
+///   reports, test names, gas budgets, step limits, tests to run after, exact gas and panic
+///   reasons, which are unset. A gas budget greater than the available gas, and an exact gas
+///   together with another gas attribute, are unset as well.
+///
+/// All other diagnostics are fatal, as a fallback could change the result of the test, or whether
+/// it is a test at all: conflicting test kinds or panic expectations, attributes on non-tests,
+/// malformed panic expectations and expected results, and malformed required features.
+pub fn try_extract_test_config_lenient(
+    db: &dyn SyntaxGroup,
+    attrs: Vec<Attribute>,
+    options: TestConfigExtractionOptions<'_>,
+) -> (Option<TestConfig>, Vec<PluginDiagnostic>) {
+    let (config, fatal_diagnostics, recoverable_diagnostics) =
+        extract_test_config(db, &attrs, options);
+    let config = match config {
+        ExtractedTestConfig::Test(config) if fatal_diagnostics.is_empty() => Some(config),
+        _ => None,
+    };
+    (config, chain!(fatal_diagnostics, recoverable_diagnostics).collect())
+}
+
+/// Extracts the configuration of a test from attributes, with the best-effort values of settings
+/// with recoverable diagnostics. Returns the configuration, the fatal diagnostics and the
+/// recoverable diagnostics. See [try_extract_test_config_lenient] for the kinds of diagnostics.
+fn extract_test_config(
+    db: &dyn SyntaxGroup,
+    attrs: &[Attribute],
+    options: TestConfigExtractionOptions<'_>,
+) -> (ExtractedTestConfig, Vec<PluginDiagnostic>, Vec<PluginDiagnostic>) {
+    let TestConfigExtractionOptions { strict, active_cfgs, function, default_gas } = options;
     let test_attr = attrs.iter().find(|attr| attr.id.as_str() == TEST_ATTR);
+    let bench_attr = attrs.iter().find(|attr| attr.id.as_str() == BENCH_ATTR);
     let ignore_attr = attrs.iter().find(|attr| attr.id.as_str() == IGNORE_ATTR);
     let available_gas_attr = attrs.iter().find(|attr| attr.id.as_str() == AVAILABLE_GAS_ATTR);
+    let no_gas_attr = attrs.iter().find(|attr| attr.id.as_str() == NO_GAS_ATTR);
     let should_panic_attr = attrs.iter().find(|attr| attr.id.as_str() == SHOULD_PANIC_ATTR);
+    let should_not_panic_attr = attrs.iter().find(|attr| attr.id.as_str() == SHOULD_NOT_PANIC_ATTR);
+    let test_case_attrs = attrs.iter().filter(|attr| attr.id.as_str() == TEST_CASE_ATTR);
+    let timeout_attr = attrs.iter().find(|attr| attr.id.as_str() == TIMEOUT_ATTR);
+    let fuzzer_attr = attrs.iter().find(|attr| attr.id.as_str() == FUZZER_ATTR);
+    let expected_result_attr = attrs.iter().find(|attr| attr.id.as_str() == EXPECTED_RESULT_ATTR);
+    let test_cfg_attr = attrs.iter().find(|attr| attr.id.as_str() == TEST_CFG_ATTR);
+    let test_tag_attrs = attrs.iter().filter(|attr| attr.id.as_str() == TEST_TAG_ATTR);
+    let test_after_attrs = attrs.iter().filter(|attr| attr.id.as_str() == TEST_AFTER_ATTR);
+    let gas_report_attr = attrs.iter().find(|attr| attr.id.as_str() == GAS_REPORT_ATTR);
+    let test_name_attr = attrs.iter().find(|attr| attr.id.as_str() == TEST_NAME_ATTR);
+    let max_gas_attr = attrs.iter().find(|attr| attr.id.as_str() == MAX_GAS_ATTR);
+    let test_isolated_attr = attrs.iter().find(|attr| attr.id.as_str() == TEST_ISOLATED_ATTR);
+    let expected_steps_attr = attrs.iter().find(|attr| attr.id.as_str() == EXPECTED_STEPS_ATTR);
+    let exact_gas_attr = attrs.iter().find(|attr| attr.id.as_str() == EXACT_GAS_ATTR);
     let mut diagnostics = vec![];
-    if let Some(attr) = test_attr {
-        if !attr.args.is_empty() {
+    let mut recoverable_diagnostics = vec![];
+    for attr_name in [
+        TEST_ATTR,
+        BENCH_ATTR,
+        IGNORE_ATTR,
+        AVAILABLE_GAS_ATTR,
+        NO_GAS_ATTR,
+        SHOULD_PANIC_ATTR,
+        SHOULD_NOT_PANIC_ATTR,
+        TIMEOUT_ATTR,
+        FUZZER_ATTR,
+        EXPECTED_RESULT_ATTR,
+        TEST_CFG_ATTR,
+        GAS_REPORT_ATTR,
+        TEST_NAME_ATTR,
+        MAX_GAS_ATTR,
+        TEST_ISOLATED_ATTR,
+        EXPECTED_STEPS_ATTR,
+        EXACT_GAS_ATTR,
+    ] {
+        // Only the first occurrence of an attribute is used, so the later ones are reported.
+        for attr in attrs.iter().filter(|attr| attr.id.as_str() == attr_name).skip(1) {
+            recoverable_diagnostics.push(PluginDiagnostic {
+                stable_ptr: attr.id_stable_ptr.untyped(),
+                message: format!("Duplicate `{attr_name}` attribute."),
+            });
+        }
+    }
+    if let (Some(_), Some(attr)) = (test_attr, bench_attr) {
+        diagnostics.push(PluginDiagnostic {
+            stable_ptr: attr.id_stable_ptr.untyped(),
+            message: format!("Attribute cannot appear together with `{TEST_ATTR}`."),
+        });
+    }
+    let kind_attr = test_attr.or(bench_attr);
+    let gated_attrs = chain!(
+        [
+            ignore_attr,
+            available_gas_attr,
+            no_gas_attr,
+            should_panic_attr,
+            should_not_panic_attr,
+            timeout_attr,
+            fuzzer_attr,
+            expected_result_attr,
+            test_cfg_attr,
+            gas_report_attr,
+            test_name_attr,
+            max_gas_attr,
+            test_isolated_attr,
+            expected_steps_attr,
+            exact_gas_attr,
+        ]
+        .into_iter()
+        .flatten(),
+        test_case_attrs.clone(),
+        test_tag_attrs.clone(),
+        test_after_attrs.clone()
+    )
+    .collect_vec();
+    if let Some(attr) = kind_attr {
+        validate_no_args(attr, &mut recoverable_diagnostics);
+    } else if strict {
+        for attr in &gated_attrs {
             diagnostics.push(PluginDiagnostic {
                 stable_ptr: attr.id_stable_ptr.untyped(),
-                message: "Attribute should not have arguments.".into(),
+                message: "Attribute should only appear on tests.".into(),
             });
         }
+    }
+    let (ignored, ignore_reason) = if let Some(attr) = ignore_attr {
+        (true, extract_ignore_reason(db, attr, &mut recoverable_diagnostics))
     } else {
-        for attr in [ignore_attr, available_gas_attr, should_panic_attr].into_iter().flatten() {
+        (false, None)
+    };
+    for attr in test_case_attrs {
+        extract_test_case_args(db, attr, &mut recoverable_diagnostics);
+    }
+    if let Some(attr) = no_gas_attr {
+        validate_no_args(attr, &mut recoverable_diagnostics);
+        if available_gas_attr.is_some() {
+            recoverable_diagnostics.push(PluginDiagnostic {
+                stable_ptr: attr.id_stable_ptr.untyped(),
+                message: format!("Attribute cannot appear together with `{AVAILABLE_GAS_ATTR}`."),
+            });
+        }
+    }
+    let n_gas_diagnostics = recoverable_diagnostics.len();
+    let (gas_mode, min_gas, max_gas) = match (available_gas_attr, no_gas_attr) {
+        (Some(attr), _) if is_gas_bounds_attr(attr) => {
+            let (min_gas, max_gas) = extract_gas_bounds(db, attr, &mut recoverable_diagnostics);
+            // The test may use up to its maximal gas, or the default gas if it is unbounded.
+            (GasMode::Limited(max_gas.unwrap_or(DEFAULT_AVAILABLE_GAS)), min_gas, max_gas)
+        }
+        (None, Some(_)) => (GasMode::Unreported, None, None),
+        _ => {
+            let available_gas = extract_available_gas(
+                available_gas_attr,
+                default_gas,
+                db,
+                &mut recoverable_diagnostics,
+            );
+            (available_gas.map_or(GasMode::Static, GasMode::Limited), None, None)
+        }
+    };
+    // A malformed gas attribute falls back to the default gas.
+    let (gas_mode, min_gas, max_gas) =
+        if recoverable_diagnostics.len() > n_gas_diagnostics && available_gas_attr.is_some() {
+            (default_gas.map_or(GasMode::Static, GasMode::Limited), None, None)
+        } else {
+            (gas_mode, min_gas, max_gas)
+        };
+    let timeout_ms =
+        timeout_attr.and_then(|attr| extract_timeout(db, attr, &mut recoverable_diagnostics));
+    let fuzzer =
+        fuzzer_attr.and_then(|attr| extract_fuzzer_config(db, attr, &mut recoverable_diagnostics));
+    let required_cfg =
+        test_cfg_attr.and_then(|attr| extract_required_cfg(db, attr, &mut diagnostics));
+    let expected_gas_report = gas_report_attr
+        .and_then(|attr| extract_expected_gas_report(db, attr, &mut recoverable_diagnostics));
+    let entry_name = test_name_attr
+        .and_then(|attr| extract_single_string_arg(db, attr, &mut recoverable_diagnostics));
+    let gas_budget = max_gas_attr.and_then(|attr| {
+        let gas_budget = extract_gas_budget(db, attr, &mut recoverable_diagnostics)?;
+        if let GasMode::Limited(available_gas) = gas_mode {
+            if gas_budget > available_gas {
+                recoverable_diagnostics.push(PluginDiagnostic {
+                    stable_ptr: attr.args_stable_ptr.untyped(),
+                    message: format!(
+                        "Gas budget {gas_budget} is greater than the available gas \
+                         {available_gas}."
+                    ),
+                });
+                return None;
+            }
+        }
+        Some(gas_budget)
+    });
+    let max_steps = expected_steps_attr
+        .and_then(|attr| extract_max_steps(db, attr, &mut recoverable_diagnostics));
+    let exact_gas = exact_gas_attr.and_then(|attr| {
+        let mut is_exclusive = true;
+        for other_attr in [available_gas_attr, no_gas_attr, max_gas_attr].into_iter().flatten() {
+            recoverable_diagnostics.push(PluginDiagnostic {
+                stable_ptr: attr.id_stable_ptr.untyped(),
+                message: format!("Attribute cannot appear together with `{}`.", other_attr.id),
+            });
+            is_exclusive = false;
+        }
+        let exact_gas = extract_exact_gas(db, attr, &mut recoverable_diagnostics)?;
+        is_exclusive.then_some(exact_gas)
+    });
+    // The limit is set high, so the consumption is measured rather than cut short.
+    let gas_mode =
+        if exact_gas.is_some() { GasMode::Limited(DEFAULT_AVAILABLE_GAS) } else { gas_mode };
+    if let Some(attr) = test_isolated_attr {
+        validate_no_args(attr, &mut recoverable_diagnostics);
+    }
+    let mut tags = vec![];
+    for attr in test_tag_attrs {
+        if let Some(tag) = extract_single_string_arg(db, attr, &mut recoverable_diagnostics) {
+            if !tags.contains(&tag) {
+                tags.push(tag);
+            }
+        }
+    }
+    let mut run_after = vec![];
+    for attr in test_after_attrs {
+        if let Some(name) = extract_single_string_arg(db, attr, &mut recoverable_diagnostics) {
+            if !run_after.contains(&name) {
+                run_after.push(name);
+            }
+        }
+    }
+    if let Some(attr) = should_not_panic_attr {
+        validate_no_args(attr, &mut recoverable_diagnostics);
+        if should_panic_attr.is_some() {
             diagnostics.push(PluginDiagnostic {
                 stable_ptr: attr.id_stable_ptr.untyped(),
-                message: "Attribute should only appear on tests.".into(),
+                message: format!("Attribute cannot appear together with `{SHOULD_PANIC_ATTR}`."),
             });
         }
     }
-    let ignored = if let Some(attr) = ignore_attr {
-        if !attr.args.is_empty() {
+    let (should_panic, panic_expectation, panic_reason) = if let Some(attr) = should_panic_attr {
+        if test_attr.is_none() && bench_attr.is_some() {
             diagnostics.push(PluginDiagnostic {
                 stable_ptr: attr.id_stable_ptr.untyped(),
-                message: "Attribute should not have arguments.".into(),
+                message: "Attribute should not appear on benchmarks.".into(),
             });
         }
-        true
-    } else {
-        false
-    };
-    let available_gas = extract_available_gas(available_gas_attr, db, &mut diagnostics);
-    let (should_panic, expected_panic_value) = if let Some(attr) = should_panic_attr {
+        let (attr, panic_reason) = extract_panic_reason(db, attr, &mut recoverable_diagnostics);
         if attr.args.is_empty() {
-            (true, None)
+            (true, None, panic_reason)
         } else {
-            (
-                true,
-                extract_panic_values(db, attr).on_none(|| {
-                    diagnostics.push(PluginDiagnostic {
-                        stable_ptr: attr.args_stable_ptr.untyped(),
-                        message: "Expected panic must be of the form `expected: <tuple of \
-                                  felt252s>`."
-                            .into(),
-                    });
-                }),
-            )
+            (true, extract_panic_expectation(db, &attr, &mut diagnostics), panic_reason)
         }
     } else {
-        (false, None)
+        (false, None, None)
     };
-    if !diagnostics.is_empty() {
-        return Err(diagnostics);
-    }
-    Ok(if test_attr.is_none() {
-        None
+    let expected_result = expected_result_attr.and_then(|attr| {
+        if should_panic_attr.is_some() {
+            diagnostics.push(PluginDiagnostic {
+                stable_ptr: attr.id_stable_ptr.untyped(),
+                message: format!("Attribute cannot appear together with `{SHOULD_PANIC_ATTR}`."),
+            });
+        }
+        extract_expected_result(db, attr, &mut diagnostics)
+    });
+    let is_inactive = |feature: &String| {
+        active_cfgs.map_or(false, |cfgs| !cfgs.contains(&Cfg::kv(FEATURE_ARG, feature.as_str())))
+    };
+    let config = if kind_attr.is_none() && (strict || gated_attrs.is_empty()) {
+        ExtractedTestConfig::NotATest
+    } else if required_cfg.as_ref().map_or(false, is_inactive) {
+        ExtractedTestConfig::Inactive
     } else {
-        Some(TestConfig {
-            available_gas,
+        ExtractedTestConfig::Test(TestConfig {
+            version: TEST_CONFIG_VERSION,
+            kind: if test_attr.is_none() && bench_attr.is_some() {
+                TestKind::Bench
+            } else {
+                TestKind::Test
+            },
+            gas_mode,
+            min_gas,
+            max_gas,
             expectation: if should_panic {
-                TestExpectation::Panics(if let Some(values) = expected_panic_value {
-                    PanicExpectation::Exact(values)
-                } else {
-                    PanicExpectation::Any
-                })
+                TestExpectation::Panics(panic_expectation.unwrap_or(PanicExpectation::Any))
+            } else if let Some(expected_result) = expected_result {
+                TestExpectation::ReturnsValue(expected_result)
             } else {
                 TestExpectation::Success
             },
             ignored,
+            ignore_reason,
+            args: vec![],
+            timeout_ms,
+            fuzzer,
+            required_cfg,
+            definition_span: function
+                .and_then(|function| TestDefinitionSpan::from_function(db, function)),
+            tags,
+            expected_gas_report,
+            entry_name,
+            gas_budget,
+            isolated: test_isolated_attr.is_some(),
+            max_steps,
+            run_after,
+            exact_gas,
+            panic_reason,
         })
-    })
+    };
+    (config, diagnostics, recoverable_diagnostics)
 }
 
-/// Extract the available gas from the attribute.
+/// Returns warning-level diagnostics for attributes that are legal but have no effect, as they
+/// configure a test that is ignored by `#[ignore]` and therefore never runs.
+///
+/// These are not returned by [try_extract_test_config], as plugin diagnostics are errors, and these
+/// settings should not fail the compilation of existing suites.
+pub fn test_config_warnings(attrs: &[Attribute]) -> Vec<PluginDiagnostic> {
+    if !attrs.iter().any(|attr| attr.id.as_str() == IGNORE_ATTR) {
+        return vec![];
+    }
+    [SHOULD_PANIC_ATTR, AVAILABLE_GAS_ATTR]
+        .into_iter()
+        .filter_map(|attr_name| attrs.iter().find(|attr| attr.id.as_str() == attr_name))
+        .map(|attr| PluginDiagnostic {
+            stable_ptr: attr.id_stable_ptr.untyped(),
+            message: format!(
+                "Attribute `{}` has no effect, as the test is ignored by `{IGNORE_ATTR}`.",
+                attr.id
+            ),
+        })
+        .collect()
+}
+
+/// Returns opt-in warning-level diagnostics for attributes that are legal but likely unintended:
+/// * An explicit available gas equal to [DEFAULT_AVAILABLE_GAS], which cannot be distinguished
+///   from the default of a test without a gas attribute.
+///
+/// Unlike [test_config_warnings], these are not expected to be reported by default, and are meant
+/// for lint-style checks.
+pub fn test_config_lints(db: &dyn SyntaxGroup, attrs: &[Attribute]) -> Vec<PluginDiagnostic> {
+    let Some(attr) = attrs.iter().find(|attr| attr.id.as_str() == AVAILABLE_GAS_ATTR) else {
+        return vec![];
+    };
+    // Malformed attributes are diagnosed by the extraction of the configuration.
+    if extract_available_gas(Some(attr), None, db, &mut vec![]) != Some(DEFAULT_AVAILABLE_GAS) {
+        return vec![];
+    }
+    vec![PluginDiagnostic {
+        stable_ptr: attr.args_stable_ptr.untyped(),
+        message: format!(
+            "Explicit gas equals the unbounded default, {DEFAULT_AVAILABLE_GAS}; consider \
+             `{STATIC_GAS_ARG}` if unbounded gas is intended."
+        ),
+    }]
+}
+
+/// Extracts the configurations of all the test instances defined by the attributes, or returns the
+/// diagnostics if the attributes are set illegally.
+///
+/// A test with `#[test_case(...)]` attributes has an instance per such attribute, called with its
+/// arguments. Otherwise, a test has a single instance as returned by [try_extract_test_config].
+/// Returns an empty vector if the attributes do not define a test, or define a test that is
+/// inactive given [TestConfigExtractionOptions::active_cfgs].
+pub fn try_extract_test_configs(
+    db: &dyn SyntaxGroup,
+    attrs: Vec<Attribute>,
+    options: TestConfigExtractionOptions<'_>,
+) -> Result<Vec<TestConfig>, Vec<PluginDiagnostic>> {
+    let test_case_attrs =
+        attrs.iter().filter(|attr| attr.id.as_str() == TEST_CASE_ATTR).cloned().collect_vec();
+    let ExtractedTestConfig::Test(config) = try_extract_test_config(db, attrs, options)? else {
+        return Ok(vec![]);
+    };
+    if test_case_attrs.is_empty() {
+        return Ok(vec![config]);
+    }
+    // The arguments were already validated by `try_extract_test_config`.
+    let mut diagnostics = vec![];
+    Ok(test_case_attrs
+        .iter()
+        .map(|attr| TestConfig {
+            args: extract_test_case_args(db, attr, &mut diagnostics).unwrap_or_default(),
+            ..config.clone()
+        })
+        .collect())
+}
+
+/// Extracts the arguments of a test case from the attribute.
 /// Adds a diagnostic if the attribute is malformed.
-/// Returns `None` if the attribute is "static", or the attribute is malformed.
-fn extract_available_gas(
-    available_gas_attr: Option<&Attribute>,
+fn extract_test_case_args(
     db: &dyn SyntaxGroup,
+    attr: &Attribute,
     diagnostics: &mut Vec<PluginDiagnostic>,
-) -> Option<usize> {
-    let Some(attr) = available_gas_attr else {
-        // If no gas is specified, we assume the reasonably large possible gas, such that infinite
-        // loops will run out of gas.
-        return Some(u32::MAX as usize);
-    };
+) -> Option<Vec<Felt252>> {
+    let mut args = vec![];
+    for arg in &attr.args {
+        let value = match &arg.variant {
+            AttributeArgVariant::Unnamed { value: ast::Expr::Literal(literal), .. } => {
+                extract_felt252_literal(db, literal, false, TEST_CASE_ARG_DESCRIPTION, diagnostics)
+            }
+            AttributeArgVariant::Unnamed { value: ast::Expr::Unary(unary), .. }
+                if matches!(unary.op(db), ast::UnaryOperator::Minus(_)) =>
+            {
+                match unary.expr(db) {
+                    ast::Expr::Literal(literal) => extract_felt252_literal(
+                        db,
+                        &literal,
+                        true,
+                        TEST_CASE_ARG_DESCRIPTION,
+                        diagnostics,
+                    ),
+                    _ => invalid_test_case_arg(attr, diagnostics),
+                }
+            }
+            AttributeArgVariant::Unnamed { value: ast::Expr::ShortString(literal), .. } => {
+                extract_short_string_felt252(db, literal, None, diagnostics)
+            }
+            _ => invalid_test_case_arg(attr, diagnostics),
+        };
+        args.push(value?);
+    }
+    Some(args)
+}
+
+/// Adds a diagnostic for a test case argument that is not an unnamed numeric or short string
+/// literal.
+fn invalid_test_case_arg(
+    attr: &Attribute,
+    diagnostics: &mut Vec<PluginDiagnostic>,
+) -> Option<Felt252> {
+    diagnostics.push(PluginDiagnostic {
+        stable_ptr: attr.args_stable_ptr.untyped(),
+        message: "Test case arguments must be unnamed numeric or short string literals.".into(),
+    });
+    None
+}
+
+/// Extracts the reason for ignoring a test from the attribute.
+/// Adds a diagnostic if the attribute is malformed.
+/// Returns `None` if no reason is given, or the attribute is malformed.
+fn extract_ignore_reason(
+    db: &dyn SyntaxGroup,
+    attr: &Attribute,
+    diagnostics: &mut Vec<PluginDiagnostic>,
+) -> Option<String> {
     let mut add_malformed_attr_diag = || {
         diagnostics.push(PluginDiagnostic {
             stable_ptr: attr.args_stable_ptr.untyped(),
-            message: format!(
-                "Attribute should have a single numeric literal argument or `{STATIC_GAS_ARG}`."
-            ),
+            message: "Attribute should have no arguments or a single `reason: \"<string>\"` \
+                      argument."
+                .into(),
         })
     };
     match &attr.args[..] {
+        [] => None,
         [
             AttributeArg {
-                variant: AttributeArgVariant::Unnamed { value: ast::Expr::Literal(literal), .. },
+                variant: AttributeArgVariant::Named { name, value: ast::Expr::String(literal), .. },
                 ..
             },
-        ] => literal.numeric_value(db).and_then(|v| v.to_usize()).on_none(add_malformed_attr_diag),
-        [
-            AttributeArg {
+        ] if name == REASON_ARG => literal.string_value(db).on_none(add_malformed_attr_diag),
+        _ => {
+            add_malformed_attr_diag();
+            None
+        }
+    }
+}
+
+/// Extract the available gas from the attribute.
+/// Adds a diagnostic if the attribute is malformed.
+/// The gas may be given as a numeric literal, optionally with a `k` or `m` unit suffix, or as the
+/// name of a constant with a numeric literal value, defined in the module of the test.
+/// Returns `None` if the attribute is "static", or the attribute is malformed.
+/// A zero gas literal is returned as `Some(0)`, distinct from the default gas of a test without the
+/// attribute.
+/// Returns `default_gas` if there is no attribute.
+fn extract_available_gas(
+    available_gas_attr: Option<&Attribute>,
+    default_gas: Option<usize>,
+    db: &dyn SyntaxGroup,
+    diagnostics: &mut Vec<PluginDiagnostic>,
+) -> Option<usize> {
+    let Some(attr) = available_gas_attr else {
+        return default_gas;
+    };
+    match &attr.args[..] {
+        [
+            AttributeArg {
+                variant: AttributeArgVariant::Unnamed { value: ast::Expr::Literal(literal), .. },
+                ..
+            },
+        ] => extract_gas_literal(db, attr, literal, diagnostics),
+        [
+            AttributeArg {
+                variant:
+                    AttributeArgVariant::Named { name, value: ast::Expr::Literal(literal), .. },
+                ..
+            },
+        ] if name == GAS_ARG => extract_gas_literal(db, attr, literal, diagnostics),
+        [
+            AttributeArg {
                 variant: AttributeArgVariant::Unnamed { value: ast::Expr::Path(path), .. },
                 ..
             },
         ] if path.as_syntax_node().get_text_without_trivia(db) == STATIC_GAS_ARG => None,
+        [
+            AttributeArg {
+                variant: AttributeArgVariant::Unnamed { value: ast::Expr::Path(path), .. },
+                ..
+            },
+        ] => {
+            let const_name = path.as_syntax_node().get_text_without_trivia(db);
+            match find_module_constant_value(db, attr, &const_name) {
+                Some(ast::Expr::Literal(literal)) => {
+                    extract_gas_literal(db, attr, &literal, diagnostics)
+                }
+                Some(_) => {
+                    diagnostics.push(PluginDiagnostic {
+                        stable_ptr: path.stable_ptr().untyped(),
+                        message: format!("Constant `{const_name}` must be a numeric literal."),
+                    });
+                    None
+                }
+                None => {
+                    diagnostics.push(PluginDiagnostic {
+                        stable_ptr: path.stable_ptr().untyped(),
+                        message: format!(
+                            "`{const_name}` is not a constant defined in the module of the test."
+                        ),
+                    });
+                    None
+                }
+            }
+        }
         _ => {
-            add_malformed_attr_diag();
+            diagnostics.push(malformed_gas_attr_diag(attr));
             None
         }
     }
 }
 
-/// Tries to extract the relevant expected panic values.
-fn extract_panic_values(db: &dyn SyntaxGroup, attr: &Attribute) -> Option<Vec<Felt252>> {
+/// Extracts the gas amount from a numeric literal of the attribute.
+/// The literal may have a `k` or `m` suffix, multiplying it by a thousand or a million
+/// respectively, e.g. `500_k`.
+/// Adds a diagnostic if the literal is malformed, has an unknown suffix, or its value does not fit
+/// in `usize`.
+fn extract_gas_literal(
+    db: &dyn SyntaxGroup,
+    attr: &Attribute,
+    literal: &ast::TerminalLiteralNumber,
+    diagnostics: &mut Vec<PluginDiagnostic>,
+) -> Option<usize> {
+    let Some((value, suffix)) = literal.numeric_value_and_suffix(db) else {
+        diagnostics.push(malformed_gas_attr_diag(attr));
+        return None;
+    };
+    let multiplier: u32 = match suffix.as_deref() {
+        None => 1,
+        Some("k") => 1_000,
+        Some("m") => 1_000_000,
+        Some(suffix) => {
+            diagnostics.push(PluginDiagnostic {
+                stable_ptr: literal.stable_ptr().untyped(),
+                message: format!("Unknown gas unit `{suffix}`, expected `k` or `m`."),
+            });
+            return None;
+        }
+    };
+    let value = value * multiplier;
+    value.to_usize().on_none(|| {
+        diagnostics.push(PluginDiagnostic {
+            stable_ptr: literal.stable_ptr().untyped(),
+            message: format!(
+                "Available gas `{value}` is out of range, the maximum allowed value is {}.",
+                usize::MAX
+            ),
+        })
+    })
+}
+
+/// Returns whether the `available_gas` attribute is of the gas bounds form, i.e. has a
+/// `min` or a `max` argument.
+fn is_gas_bounds_attr(attr: &Attribute) -> bool {
+    attr.args.iter().any(|arg| {
+        matches!(
+            &arg.variant,
+            AttributeArgVariant::Named { name, .. } if name == MIN_GAS_ARG || name == MAX_GAS_ARG
+        )
+    })
+}
+
+/// Extracts the bounds on the gas consumption from an attribute of the form
+/// `#[available_gas(min: <min>, max: <max>)]`, where each of the bounds is optional.
+/// Adds a diagnostic if the attribute is malformed, or if the minimum exceeds the maximum.
+fn extract_gas_bounds(
+    db: &dyn SyntaxGroup,
+    attr: &Attribute,
+    diagnostics: &mut Vec<PluginDiagnostic>,
+) -> (Option<usize>, Option<usize>) {
+    let mut min_gas = None;
+    let mut max_gas = None;
+    for arg in &attr.args {
+        match &arg.variant {
+            AttributeArgVariant::Named { name, value: ast::Expr::Literal(literal), .. }
+                if name == MIN_GAS_ARG && min_gas.is_none() =>
+            {
+                min_gas = extract_gas_literal(db, attr, literal, diagnostics);
+            }
+            AttributeArgVariant::Named { name, value: ast::Expr::Literal(literal), .. }
+                if name == MAX_GAS_ARG && max_gas.is_none() =>
+            {
+                max_gas = extract_gas_literal(db, attr, literal, diagnostics);
+            }
+            _ => diagnostics.push(PluginDiagnostic {
+                stable_ptr: arg.arg_stable_ptr.untyped(),
+                message: format!(
+                    "Gas bounds must be of the form `{MIN_GAS_ARG}: <number>, {MAX_GAS_ARG}: \
+                     <number>`, and cannot be combined with a single gas value."
+                ),
+            }),
+        }
+    }
+    if let (Some(min), Some(max)) = (min_gas, max_gas) {
+        if min > max {
+            diagnostics.push(PluginDiagnostic {
+                stable_ptr: attr.args_stable_ptr.untyped(),
+                message: format!("Minimal gas {min} is greater than maximal gas {max}."),
+            });
+        }
+    }
+    (min_gas, max_gas)
+}
+
+/// Returns the diagnostic for a malformed `available_gas` attribute.
+/// The diagnostic points at the argument expression if the attribute has a single argument, and at
+/// all the arguments otherwise.
+fn malformed_gas_attr_diag(attr: &Attribute) -> PluginDiagnostic {
+    let stable_ptr = match &attr.args[..] {
+        [
+            AttributeArg {
+                variant:
+                    AttributeArgVariant::Unnamed { value_stable_ptr, .. }
+                    | AttributeArgVariant::Named { value_stable_ptr, .. },
+                ..
+            },
+        ] => value_stable_ptr.untyped(),
+        [arg] => arg.arg_stable_ptr.untyped(),
+        _ => attr.args_stable_ptr.untyped(),
+    };
+    PluginDiagnostic {
+        stable_ptr,
+        message: format!(
+            "Attribute should have a single numeric literal argument or `{STATIC_GAS_ARG}`."
+        ),
+    }
+}
+
+/// Extracts the expected gas usage of the test from the `gas_report` attribute.
+/// Adds a diagnostic if the attribute is malformed.
+fn extract_expected_gas_report(
+    db: &dyn SyntaxGroup,
+    attr: &Attribute,
+    diagnostics: &mut Vec<PluginDiagnostic>,
+) -> Option<usize> {
+    extract_single_numeric_arg(
+        db,
+        attr,
+        Some(EXPECTED_ARG),
+        false,
+        &format!(
+            "Attribute should have a single `{EXPECTED_ARG}: <number>` argument, the expected gas \
+             usage."
+        ),
+        diagnostics,
+    )
+}
+
+/// Extracts the gas budget of the test from the `max_gas` attribute.
+/// Adds a diagnostic if the attribute is malformed.
+fn extract_gas_budget(
+    db: &dyn SyntaxGroup,
+    attr: &Attribute,
+    diagnostics: &mut Vec<PluginDiagnostic>,
+) -> Option<usize> {
+    extract_single_numeric_arg(
+        db,
+        attr,
+        None,
+        false,
+        "Attribute should have a single numeric literal argument, the gas budget.",
+        diagnostics,
+    )
+}
+
+/// Extracts the maximal number of steps of the test from the `expected_steps` attribute.
+/// Adds a diagnostic if the attribute is malformed.
+fn extract_max_steps(
+    db: &dyn SyntaxGroup,
+    attr: &Attribute,
+    diagnostics: &mut Vec<PluginDiagnostic>,
+) -> Option<usize> {
+    extract_single_numeric_arg(
+        db,
+        attr,
+        Some(MAX_STEPS_ARG),
+        true,
+        &format!(
+            "Attribute should have a single `{MAX_STEPS_ARG}: <positive number>` argument, the \
+             maximal number of steps."
+        ),
+        diagnostics,
+    )
+}
+
+/// Extracts the exact gas consumption of the test from the `exact_gas` attribute.
+/// Adds a diagnostic if the attribute is malformed.
+fn extract_exact_gas(
+    db: &dyn SyntaxGroup,
+    attr: &Attribute,
+    diagnostics: &mut Vec<PluginDiagnostic>,
+) -> Option<usize> {
+    extract_single_numeric_arg(
+        db,
+        attr,
+        None,
+        false,
+        "Attribute should have a single numeric literal argument, the exact gas consumption.",
+        diagnostics,
+    )
+}
+
+/// Extracts the timeout, in milliseconds, from the attribute.
+/// Adds a diagnostic if the attribute is malformed.
+fn extract_timeout(
+    db: &dyn SyntaxGroup,
+    attr: &Attribute,
+    diagnostics: &mut Vec<PluginDiagnostic>,
+) -> Option<u64> {
+    extract_single_numeric_arg(
+        db,
+        attr,
+        None,
+        true,
+        "Attribute should have a single positive numeric literal argument, the timeout in \
+         milliseconds.",
+        diagnostics,
+    )
+}
+
+/// Extracts the fuzzer configuration from the attribute.
+/// Adds a diagnostic if the attribute is malformed, or is missing the number of runs.
+fn extract_fuzzer_config(
+    db: &dyn SyntaxGroup,
+    attr: &Attribute,
+    diagnostics: &mut Vec<PluginDiagnostic>,
+) -> Option<FuzzerConfig> {
+    let mut runs = None;
+    let mut seed = None;
+    for arg in &attr.args {
+        match &arg.variant {
+            AttributeArgVariant::Named { name, value, .. }
+                if name == FUZZER_RUNS_ARG && runs.is_none() =>
+            {
+                runs = Some(extract_fuzzer_arg(db, value, diagnostics)?);
+            }
+            AttributeArgVariant::Named { name, value, .. }
+                if name == FUZZER_SEED_ARG && seed.is_none() =>
+            {
+                seed = Some(extract_fuzzer_arg(db, value, diagnostics)?);
+            }
+            _ => {
+                diagnostics.push(PluginDiagnostic {
+                    stable_ptr: arg.arg_stable_ptr.untyped(),
+                    message: format!(
+                        "Attribute should have a `{FUZZER_RUNS_ARG}: <number>` argument, and an \
+                         optional `{FUZZER_SEED_ARG}: <number>` argument."
+                    ),
+                });
+                return None;
+            }
+        }
+    }
+    let Some(runs) = runs else {
+        diagnostics.push(PluginDiagnostic {
+            stable_ptr: attr.args_stable_ptr.untyped(),
+            message: format!("Attribute is missing the `{FUZZER_RUNS_ARG}` argument."),
+        });
+        return None;
+    };
+    Some(FuzzerConfig { runs, seed })
+}
+
+/// Extracts the name of the feature required by the `test_cfg` attribute.
+/// Adds a diagnostic if the attribute is malformed, or is missing the `feature` argument.
+fn extract_required_cfg(
+    db: &dyn SyntaxGroup,
+    attr: &Attribute,
+    diagnostics: &mut Vec<PluginDiagnostic>,
+) -> Option<String> {
+    if let [AttributeArg {
+        variant: AttributeArgVariant::Named { name, value: ast::Expr::String(literal), .. },
+        ..
+    }] = &attr.args[..]
+    {
+        if name == FEATURE_ARG {
+            if let Some(feature) = literal.string_value(db).filter(|feature| !feature.is_empty()) {
+                return Some(feature);
+            }
+        }
+    }
+    let has_feature_arg = attr.args.iter().any(|arg| {
+        matches!(&arg.variant, AttributeArgVariant::Named { name, .. } if name == FEATURE_ARG)
+    });
+    diagnostics.push(PluginDiagnostic {
+        stable_ptr: attr.args_stable_ptr.untyped(),
+        message: if has_feature_arg {
+            format!("Attribute should have a single `{FEATURE_ARG}: \"<string>\"` argument.")
+        } else {
+            format!("Attribute is missing the `{FEATURE_ARG}` argument.")
+        },
+    });
+    None
+}
+
+/// Extracts the single string argument of an attribute, such as the tag of `#[test_tag("<tag>")]`
+/// or the name of `#[test_name("<name>")]`.
+/// Adds a diagnostic if the attribute does not have a single non-empty string argument.
+fn extract_single_string_arg(
+    db: &dyn SyntaxGroup,
+    attr: &Attribute,
+    diagnostics: &mut Vec<PluginDiagnostic>,
+) -> Option<String> {
+    if let [AttributeArg {
+        variant: AttributeArgVariant::Unnamed { value: ast::Expr::String(literal), .. },
+        ..
+    }] = &attr.args[..]
+    {
+        if let Some(value) = literal.string_value(db).filter(|value| !value.is_empty()) {
+            return Some(value);
+        }
+    }
+    diagnostics.push(PluginDiagnostic {
+        stable_ptr: attr.args_stable_ptr.untyped(),
+        message: "Attribute should have a single non-empty string argument.".into(),
+    });
+    None
+}
+
+/// Extracts the single numeric literal argument of an attribute, which is named `arg_name` if
+/// given, or unnamed otherwise, such as the gas budget of `#[max_gas(<gas>)]`.
+/// Adds a diagnostic with `message` if the attribute does not have such an argument, or its value
+/// does not fit in `T`, or is zero while `positive` is set.
+fn extract_single_numeric_arg<T: TryFrom<u64>>(
+    db: &dyn SyntaxGroup,
+    attr: &Attribute,
+    arg_name: Option<&str>,
+    positive: bool,
+    message: &str,
+    diagnostics: &mut Vec<PluginDiagnostic>,
+) -> Option<T> {
+    let literal = match &attr.args[..] {
+        [AttributeArg { variant, .. }] => match (variant, arg_name) {
+            (AttributeArgVariant::Unnamed { value: ast::Expr::Literal(literal), .. }, None) => {
+                Some(literal)
+            }
+            (
+                AttributeArgVariant::Named { name, value: ast::Expr::Literal(literal), .. },
+                Some(arg_name),
+            ) if name == arg_name => Some(literal),
+            _ => None,
+        },
+        _ => None,
+    };
+    literal
+        .and_then(|literal| literal.numeric_value(db)?.to_u64())
+        .filter(|value| !positive || *value > 0)
+        .and_then(|value| T::try_from(value).ok())
+        .on_none(|| {
+            diagnostics.push(PluginDiagnostic {
+                stable_ptr: attr.args_stable_ptr.untyped(),
+                message: message.into(),
+            })
+        })
+}
+
+/// Adds a diagnostic if the attribute has arguments, such as `#[test_isolated]`.
+fn validate_no_args(attr: &Attribute, diagnostics: &mut Vec<PluginDiagnostic>) {
+    if !attr.args.is_empty() {
+        diagnostics.push(PluginDiagnostic {
+            stable_ptr: attr.id_stable_ptr.untyped(),
+            message: "Attribute should not have arguments.".into(),
+        });
+    }
+}
+
+/// Extracts the value of a numeric argument of the fuzzer attribute.
+/// Adds a diagnostic if the value is not a numeric literal in the range of `u64`.
+fn extract_fuzzer_arg(
+    db: &dyn SyntaxGroup,
+    value: &ast::Expr,
+    diagnostics: &mut Vec<PluginDiagnostic>,
+) -> Option<u64> {
+    let numeric_value = match value {
+        ast::Expr::Literal(literal) => literal.numeric_value(db).and_then(|v| v.to_u64()),
+        _ => None,
+    };
+    numeric_value.on_none(|| {
+        diagnostics.push(PluginDiagnostic {
+            stable_ptr: value.stable_ptr().untyped(),
+            message: format!("Fuzzer argument must be a numeric literal of at most {}.", u64::MAX),
+        })
+    })
+}
+
+/// Returns the items of the module containing `attr`.
+fn find_module_items(db: &dyn SyntaxGroup, attr: &Attribute) -> Option<Vec<ast::Item>> {
+    let mut node = attr.stable_ptr.untyped().lookup(db);
+    let items = loop {
+        node = node.parent()?;
+        match node.kind(db) {
+            SyntaxKind::ModuleBody => break ast::ModuleBody::from_syntax_node(db, node).items(db),
+            SyntaxKind::SyntaxFile => break ast::SyntaxFile::from_syntax_node(db, node).items(db),
+            _ => {}
+        }
+    };
+    Some(items.elements(db))
+}
+
+/// Finds the value of the constant named `name` among the items of the module containing `attr`.
+/// Returns `None` if there is no such constant.
+fn find_module_constant_value(
+    db: &dyn SyntaxGroup,
+    attr: &Attribute,
+    name: &str,
+) -> Option<ast::Expr> {
+    find_module_items(db, attr)?.into_iter().find_map(|item| match item {
+        ast::Item::Constant(constant) if constant.name(db).text(db) == name => {
+            Some(constant.value(db))
+        }
+        _ => None,
+    })
+}
+
+/// Finds the index of the variant named `variant_name` of the enum named `enum_name`, among the
+/// items of the module containing `attr`. This index is the serialization of the variant by the
+/// derived `Serde` implementation, if the variant has no data.
+/// Returns `None` if there is no such enum, or no such variant without data.
+fn find_module_unit_variant_index(
+    db: &dyn SyntaxGroup,
+    attr: &Attribute,
+    enum_name: &str,
+    variant_name: &str,
+) -> Option<usize> {
+    let item_enum = find_module_items(db, attr)?.into_iter().find_map(|item| match item {
+        ast::Item::Enum(item_enum) if item_enum.name(db).text(db) == enum_name => Some(item_enum),
+        _ => None,
+    })?;
+    let variants = item_enum.variants(db).elements(db);
+    let index = variants.iter().position(|variant| variant.name(db).text(db) == variant_name)?;
+    let is_unit = match variants[index].type_clause(db) {
+        ast::OptionTypeClause::Empty(_) => true,
+        ast::OptionTypeClause::TypeClause(type_clause) => matches!(
+            type_clause.ty(db),
+            ast::Expr::Tuple(tuple) if tuple.expressions(db).elements(db).is_empty()
+        ),
+    };
+    is_unit.then_some(index)
+}
+
+/// Tries to extract the expected panic from the attribute.
+/// Adds a diagnostic if the attribute is malformed.
+fn extract_panic_expectation(
+    db: &dyn SyntaxGroup,
+    attr: &Attribute,
+    diagnostics: &mut Vec<PluginDiagnostic>,
+) -> Option<PanicExpectation> {
+    let has_named_arg = |arg_name: &str| {
+        attr.args.iter().any(|arg| {
+            matches!(&arg.variant, AttributeArgVariant::Named { name, .. } if name == arg_name)
+        })
+    };
+    if has_named_arg(EXPECTED_ARG) && has_named_arg(EXPECTED_CONTAINS_ARG) {
+        diagnostics.push(PluginDiagnostic {
+            stable_ptr: attr.args_stable_ptr.untyped(),
+            message: format!(
+                "`{EXPECTED_ARG}` and `{EXPECTED_CONTAINS_ARG}` cannot be used together."
+            ),
+        });
+        return None;
+    }
+    for arg in &attr.args {
+        let AttributeArgVariant::Named { name, name_stable_ptr, .. } = &arg.variant else {
+            continue;
+        };
+        if ![EXPECTED_ARG, EXPECTED_CONTAINS_ARG, EXPECTED_ANY_ARG].contains(&name.as_str()) {
+            diagnostics.push(PluginDiagnostic {
+                stable_ptr: name_stable_ptr.untyped(),
+                message: format!(
+                    "Unknown argument `{name}`, expected `{EXPECTED_ARG}`, \
+                     `{EXPECTED_CONTAINS_ARG}`, `{EXPECTED_ANY_ARG}` or `{REASON_ARG}`."
+                ),
+            });
+            return None;
+        }
+    }
+    // A single positional argument is most likely the expected values, missing their name.
+    if let [
+        AttributeArg { variant: AttributeArgVariant::Unnamed { value, .. }, arg_stable_ptr, .. },
+    ] = &attr.args[..]
+    {
+        diagnostics.push(PluginDiagnostic {
+            stable_ptr: arg_stable_ptr.untyped(),
+            message: format!(
+                "Expected panic values must be passed by name, as in `{EXPECTED_ARG}: {}`.",
+                value.as_syntax_node().get_text_without_trivia(db)
+            ),
+        });
+        return None;
+    }
     let [AttributeArg { variant: AttributeArgVariant::Named { name, value: panics, .. }, .. }] =
         &attr.args[..]
     else {
+        diagnostics.push(malformed_panic_attr_diag(attr));
         return None;
     };
-    if name != "expected" {
+    match name.as_str() {
+        EXPECTED_ARG
+            if matches!(
+                panics,
+                ast::Expr::Path(path)
+                    if path.as_syntax_node().get_text_without_trivia(db) == OUT_OF_GAS_ARG
+            ) =>
+        {
+            Some(PanicExpectation::OutOfGas)
+        }
+        EXPECTED_ARG
+            if matches!(
+                panics,
+                ast::Expr::Path(path)
+                    if path.as_syntax_node().get_text_without_trivia(db) == EMPTY_PANIC_ARG
+            ) =>
+        {
+            Some(PanicExpectation::Exact(vec![]))
+        }
+        EXPECTED_ARG => {
+            let values =
+                extract_panic_values(db, attr, panics, malformed_panic_attr_diag, diagnostics)?;
+            // An empty expectation only matches a panic without values, which is rarely intended.
+            if values.is_empty() {
+                diagnostics.push(PluginDiagnostic {
+                    stable_ptr: panics.stable_ptr().untyped(),
+                    message: format!(
+                        "Expected panic is empty, and would only match a panic without values. \
+                         Remove `{EXPECTED_ARG}` to accept any panic, provide the expected \
+                         values, or use `{EXPECTED_ARG}: {EMPTY_PANIC_ARG}` to accept only an \
+                         empty panic."
+                    ),
+                });
+                return None;
+            }
+            Some(PanicExpectation::Exact(values))
+        }
+        EXPECTED_CONTAINS_ARG => {
+            extract_panic_values(db, attr, panics, malformed_panic_attr_diag, diagnostics)
+                .map(PanicExpectation::Contains)
+        }
+        EXPECTED_ANY_ARG => {
+            extract_any_of_panic_values(db, attr, panics, diagnostics).map(PanicExpectation::AnyOf)
+        }
+        _ => {
+            diagnostics.push(malformed_panic_attr_diag(attr));
+            None
+        }
+    }
+}
+
+/// Splits the `reason` argument out of a `should_panic` attribute. Returns the attribute without
+/// it, and the reason, if given.
+/// Adds a diagnostic if the reason is not a non-empty string. Only the first `reason` argument is
+/// used.
+fn extract_panic_reason(
+    db: &dyn SyntaxGroup,
+    attr: &Attribute,
+    diagnostics: &mut Vec<PluginDiagnostic>,
+) -> (Attribute, Option<String>) {
+    let (reason_args, args): (Vec<_>, Vec<_>) = attr.args.iter().cloned().partition(
+        |arg| matches!(&arg.variant, AttributeArgVariant::Named { name, .. } if name == REASON_ARG),
+    );
+    let panic_reason = reason_args.first().and_then(|arg| {
+        let panic_reason = match &arg.variant {
+            AttributeArgVariant::Named { value: ast::Expr::String(literal), .. } => {
+                literal.string_value(db).filter(|reason| !reason.is_empty())
+            }
+            _ => None,
+        };
+        panic_reason.on_none(|| {
+            diagnostics.push(PluginDiagnostic {
+                stable_ptr: arg.arg_stable_ptr.untyped(),
+                message: format!("`{REASON_ARG}` must be a non-empty string."),
+            })
+        })
+    });
+    (Attribute { args, ..attr.clone() }, panic_reason)
+}
+
+/// Returns the diagnostic for a malformed `should_panic` attribute.
+fn malformed_panic_attr_diag(attr: &Attribute) -> PluginDiagnostic {
+    PluginDiagnostic {
+        stable_ptr: attr.args_stable_ptr.untyped(),
+        message: format!(
+            "Expected panic must be of the form `{EXPECTED_ARG}: <tuple of felt252s>`, \
+             `{EXPECTED_ARG}: {ARRAY_MACRO}![<felt252s>]`, `{EXPECTED_ARG}: \"<string>\"` or \
+             `{EXPECTED_ARG}: <enum>::<variant>`, \
+             optionally using `{EXPECTED_CONTAINS_ARG}` instead of `{EXPECTED_ARG}`, \
+             `{EXPECTED_ARG}: {OUT_OF_GAS_ARG}`, `{EXPECTED_ARG}: {EMPTY_PANIC_ARG}`, or \
+             `{EXPECTED_ANY_ARG}: {ARRAY_MACRO}![<expected panics>]`."
+        ),
+    }
+}
+
+/// Tries to extract the alternative expected panics of an `expected_any` argument, given as a
+/// tuple or an `array!` of expected panics, each of the forms accepted by [extract_panic_values].
+/// Adds a diagnostic if the alternatives are malformed, or if one of them is not a nested expected
+/// panic.
+fn extract_any_of_panic_values(
+    db: &dyn SyntaxGroup,
+    attr: &Attribute,
+    panics: &ast::Expr,
+    diagnostics: &mut Vec<PluginDiagnostic>,
+) -> Option<Vec<Vec<Felt252>>> {
+    let options = match panics {
+        ast::Expr::Tuple(panics) => panics.expressions(db).elements(db),
+        ast::Expr::InlineMacro(inline_macro)
+            if inline_macro.path(db).as_syntax_node().get_text_without_trivia(db)
+                == ARRAY_MACRO =>
+        {
+            let ast::WrappedArgList::BracketedArgList(args) = inline_macro.arguments(db) else {
+                diagnostics.push(malformed_panic_attr_diag(attr));
+                return None;
+            };
+            let mut options = vec![];
+            for arg in args.arguments(db).elements(db) {
+                match arg.arg_clause(db) {
+                    ast::ArgClause::Unnamed(clause)
+                        if arg.modifiers(db).elements(db).is_empty() =>
+                    {
+                        options.push(clause.value(db));
+                    }
+                    _ => {
+                        diagnostics.push(malformed_panic_attr_diag(attr));
+                        return None;
+                    }
+                }
+            }
+            options
+        }
+        _ => {
+            diagnostics.push(malformed_panic_attr_diag(attr));
+            return None;
+        }
+    };
+    if options.is_empty() {
+        diagnostics.push(malformed_panic_attr_diag(attr));
         return None;
     }
-    let ast::Expr::Tuple(panics) = panics else { return None };
-    panics
-        .expressions(db)
-        .elements(db)
-        .into_iter()
-        .map(|value| match value {
-            ast::Expr::Literal(literal) => {
-                Some(literal.numeric_value(db).unwrap_or_default().into())
+    options
+        .iter()
+        .enumerate()
+        .map(|(index, option)| match option {
+            ast::Expr::Tuple(_) | ast::Expr::InlineMacro(_) | ast::Expr::String(_) => {
+                extract_panic_values(db, attr, option, malformed_panic_attr_diag, diagnostics)
             }
-            ast::Expr::ShortString(literal) => {
-                Some(literal.numeric_value(db).unwrap_or_default().into())
+            _ => {
+                diagnostics.push(PluginDiagnostic {
+                    stable_ptr: option.stable_ptr().untyped(),
+                    message: format!(
+                        "Expected panic at index {index} must be a tuple of felt252s, \
+                         `{ARRAY_MACRO}![<felt252s>]` or a string."
+                    ),
+                });
+                None
             }
-            _ => None,
         })
-        .collect::<Option<Vec<_>>>()
+        // Collecting all the options first, to report the diagnostics of all of them.
+        .collect_vec()
+        .into_iter()
+        .collect()
+}
+
+/// Extracts the expected return values of the test from the attribute.
+/// Adds a diagnostic if the attribute is malformed.
+fn extract_expected_result(
+    db: &dyn SyntaxGroup,
+    attr: &Attribute,
+    diagnostics: &mut Vec<PluginDiagnostic>,
+) -> Option<Vec<Felt252>> {
+    let [AttributeArg { variant: AttributeArgVariant::Unnamed { value, .. }, .. }] = &attr.args[..]
+    else {
+        diagnostics.push(malformed_expected_result_attr_diag(attr));
+        return None;
+    };
+    extract_panic_values(db, attr, value, malformed_expected_result_attr_diag, diagnostics)
+}
+
+/// Returns the diagnostic for a malformed `expected_result` attribute.
+fn malformed_expected_result_attr_diag(attr: &Attribute) -> PluginDiagnostic {
+    PluginDiagnostic {
+        stable_ptr: attr.args_stable_ptr.untyped(),
+        message: format!(
+            "Expected result must be of the form `<tuple of felt252s>` or \
+             `{ARRAY_MACRO}![<felt252s>]`."
+        ),
+    }
+}
+
+/// Tries to extract the relevant expected panic values.
+/// A path of a variant without data of an enum defined in the module of the test is its index,
+/// which is the serialization of the variant.
+/// Adds a diagnostic if the values are malformed, using `malformed_diag` for a malformed form of
+/// the values.
+fn extract_panic_values(
+    db: &dyn SyntaxGroup,
+    attr: &Attribute,
+    panics: &ast::Expr,
+    malformed_diag: fn(&Attribute) -> PluginDiagnostic,
+    diagnostics: &mut Vec<PluginDiagnostic>,
+) -> Option<Vec<Felt252>> {
+    match panics {
+        ast::Expr::String(literal) => literal
+            .string_value(db)
+            .map(|value| encode_byte_array_panic(&value))
+            .on_none(|| diagnostics.push(malformed_diag(attr))),
+        ast::Expr::Tuple(panics) => panics
+            .expressions(db)
+            .elements(db)
+            .iter()
+            .enumerate()
+            .map(|(index, value)| extract_panic_value(db, attr, index, value, diagnostics))
+            // Collecting all the elements first, to report the diagnostics of all of them.
+            .collect_vec()
+            .into_iter()
+            .collect(),
+        ast::Expr::InlineMacro(inline_macro)
+            if inline_macro.path(db).as_syntax_node().get_text_without_trivia(db)
+                == ARRAY_MACRO =>
+        {
+            let ast::WrappedArgList::BracketedArgList(args) = inline_macro.arguments(db) else {
+                diagnostics.push(malformed_diag(attr));
+                return None;
+            };
+            args.arguments(db)
+                .elements(db)
+                .iter()
+                .enumerate()
+                .map(|(index, arg)| match arg.arg_clause(db) {
+                    ast::ArgClause::Unnamed(clause)
+                        if arg.modifiers(db).elements(db).is_empty() =>
+                    {
+                        extract_panic_value(db, attr, index, &clause.value(db), diagnostics)
+                    }
+                    _ => {
+                        let stable_ptr = arg.stable_ptr().untyped();
+                        diagnostics.push(non_felt252_element_diag(index, stable_ptr));
+                        None
+                    }
+                })
+                // Collecting all the elements first, to report the diagnostics of all of them.
+                .collect_vec()
+                .into_iter()
+                .collect()
+        }
+        // A path of an enum variant, as in `MyError::Overflow`.
+        ast::Expr::Path(path) if path.elements(db).len() > 1 => {
+            let variant_index = match &path.elements(db)[..] {
+                [enum_segment, variant_segment] => find_module_unit_variant_index(
+                    db,
+                    attr,
+                    &enum_segment.identifier(db),
+                    &variant_segment.identifier(db),
+                ),
+                _ => None,
+            };
+            variant_index.map(|index| vec![Felt252::from(index)]).on_none(|| {
+                diagnostics.push(PluginDiagnostic {
+                    stable_ptr: path.stable_ptr().untyped(),
+                    message: format!(
+                        "`{}` is not a variant without data of an enum defined in the module of \
+                         the test.",
+                        path.as_syntax_node().get_text_without_trivia(db)
+                    ),
+                })
+            })
+        }
+        _ => {
+            diagnostics.push(malformed_diag(attr));
+            None
+        }
+    }
+}
+
+/// Extracts a single value of the expected panic values.
+/// The value may be a felt252 literal, or the name of a constant with such a value, defined in the
+/// module of the test.
+/// Adds a diagnostic if the value is not a felt252 literal, pointing at the value and naming its
+/// `index` among the values.
+fn extract_panic_value(
+    db: &dyn SyntaxGroup,
+    attr: &Attribute,
+    index: usize,
+    value: &ast::Expr,
+    diagnostics: &mut Vec<PluginDiagnostic>,
+) -> Option<Felt252> {
+    match value {
+        ast::Expr::Literal(literal) => {
+            extract_felt252_literal(db, literal, false, PANIC_VALUE_DESCRIPTION, diagnostics)
+        }
+        ast::Expr::Unary(unary) if matches!(unary.op(db), ast::UnaryOperator::Minus(_)) => {
+            match unary.expr(db) {
+                ast::Expr::Literal(literal) => extract_felt252_literal(
+                    db,
+                    &literal,
+                    true,
+                    PANIC_VALUE_DESCRIPTION,
+                    diagnostics,
+                ),
+                _ => {
+                    diagnostics.push(non_felt252_element_diag(index, unary.stable_ptr().untyped()));
+                    None
+                }
+            }
+        }
+        ast::Expr::ShortString(literal) => extract_short_string_felt252(
+            db,
+            literal,
+            Some(&format!(
+                "Use a string of the form `{EXPECTED_ARG}: \"<string>\"` for longer messages."
+            )),
+            diagnostics,
+        ),
+        ast::Expr::Path(path)
+            if path.as_syntax_node().get_text_without_trivia(db) == OUT_OF_GAS_ARG =>
+        {
+            diagnostics.push(PluginDiagnostic {
+                stable_ptr: path.stable_ptr().untyped(),
+                message: format!(
+                    "`{OUT_OF_GAS_ARG}` must be the sole expected panic, of the form \
+                     `{EXPECTED_ARG}: {OUT_OF_GAS_ARG}`, and cannot be combined with panic values."
+                ),
+            });
+            None
+        }
+        ast::Expr::Path(path) => {
+            let const_name = path.as_syntax_node().get_text_without_trivia(db);
+            match find_module_constant_value(db, attr, &const_name) {
+                // A constant defined by another path is not followed, avoiding cyclic definitions.
+                Some(value) if !matches!(value, ast::Expr::Path(_)) => {
+                    extract_panic_value(db, attr, index, &value, diagnostics)
+                }
+                _ => {
+                    diagnostics.push(PluginDiagnostic {
+                        stable_ptr: path.stable_ptr().untyped(),
+                        message: format!(
+                            "`{const_name}` is not a felt252 constant defined in the module of \
+                             the test."
+                        ),
+                    });
+                    None
+                }
+            }
+        }
+        ast::Expr::String(literal) => {
+            diagnostics.push(PluginDiagnostic {
+                stable_ptr: literal.stable_ptr().untyped(),
+                message: "A string expected panic must be the sole argument, of the form \
+                          `expected: \"<string>\"`."
+                    .into(),
+            });
+            None
+        }
+        _ => {
+            diagnostics.push(non_felt252_element_diag(index, value.stable_ptr().untyped()));
+            None
+        }
+    }
+}
+
+/// Extracts a felt252 from a short string literal.
+/// Adds a diagnostic if the literal is malformed, or is too long to fit in a felt252, followed by
+/// `long_string_hint` in the latter case, if given.
+fn extract_short_string_felt252(
+    db: &dyn SyntaxGroup,
+    literal: &ast::TerminalShortString,
+    long_string_hint: Option<&str>,
+    diagnostics: &mut Vec<PluginDiagnostic>,
+) -> Option<Felt252> {
+    let Some(value) = literal.string_value(db) else {
+        diagnostics.push(PluginDiagnostic {
+            stable_ptr: literal.stable_ptr().untyped(),
+            message: format!("Malformed short string `{}`.", literal.text(db)),
+        });
+        return None;
+    };
+    if value.len() > BYTES_IN_BYTES31 {
+        diagnostics.push(PluginDiagnostic {
+            stable_ptr: literal.stable_ptr().untyped(),
+            message: format!(
+                "Short string `{}` is {} bytes long, exceeding the {BYTES_IN_BYTES31} bytes of a \
+                 felt252.{}",
+                literal.text(db),
+                value.len(),
+                long_string_hint.map(|hint| format!(" {hint}")).unwrap_or_default()
+            ),
+        });
+        return None;
+    }
+    Some(Felt252::from_bytes_be(value.as_bytes()))
+}
+
+/// Returns the diagnostic for an expected value, at `index` among the values, that is not a
+/// felt252.
+fn non_felt252_element_diag(index: usize, stable_ptr: SyntaxStablePtrId) -> PluginDiagnostic {
+    PluginDiagnostic {
+        stable_ptr,
+        message: format!("Expected value at index {index} must be a felt252 literal."),
+    }
+}
+
+/// Extracts a felt252 from a numeric literal, negated in the field if `negate` is set.
+/// Adds a diagnostic if the literal is malformed or out of the range of felt252, naming the literal
+/// by `description`.
+fn extract_felt252_literal(
+    db: &dyn SyntaxGroup,
+    literal: &ast::TerminalLiteralNumber,
+    negate: bool,
+    description: &str,
+    diagnostics: &mut Vec<PluginDiagnostic>,
+) -> Option<Felt252> {
+    let value = literal.numeric_value(db).filter(|value| value.magnitude() < &Felt252::prime());
+    let Some(value) = value else {
+        diagnostics.push(PluginDiagnostic {
+            stable_ptr: literal.stable_ptr().untyped(),
+            message: format!(
+                "{description} `{}` is not a valid felt252 literal.",
+                literal.text(db)
+            ),
+        });
+        return None;
+    };
+    Some(Felt252::from(if negate { -value } else { value }))
+}
+
+/// Encodes `value` as the panic data of a panic with a `ByteArray` message.
+///
+/// The encoding is the `ByteArray` magic, followed by the number of full words, the full 31-byte
+/// words, the pending word and the number of bytes in the pending word. These are the felts of a
+/// panic with the `ByteArray` of `value`, so an expected string can be compared to them directly.
+pub fn encode_byte_array_panic(value: &str) -> Vec<Felt252> {
+    let bytes = value.as_bytes();
+    let full_words = bytes.chunks_exact(BYTES_IN_BYTES31);
+    let pending_word = full_words.remainder();
+    chain!(
+        [
+            Felt252::parse_bytes(BYTE_ARRAY_MAGIC.as_bytes(), 16).unwrap(),
+            Felt252::from(full_words.len()),
+        ],
+        full_words.map(Felt252::from_bytes_be),
+        [Felt252::from_bytes_be(pending_word), Felt252::from(pending_word.len())]
+    )
+    .collect()
 }
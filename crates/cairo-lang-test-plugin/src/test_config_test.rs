@@ -0,0 +1,844 @@
+use std::path::PathBuf;
+
+use cairo_felt::Felt252;
+use cairo_lang_filesystem::ids::FileId;
+use cairo_lang_parser::utils::{get_syntax_file_and_diagnostics, SimpleParserDatabase};
+use cairo_lang_syntax::attribute::structured::{Attribute, AttributeListStructurize};
+use cairo_lang_syntax::node::ast;
+use itertools::Itertools;
+
+use super::{
+    encode_byte_array_panic, test_config_lints, test_config_warnings, try_extract_test_config,
+    try_extract_test_config_lenient, try_extract_test_configs, ExtractedTestConfig, GasMode,
+    PanicExpectation, PartialTestConfig, TestConfig, TestConfigExtractionOptions,
+    TestDefinitionSpan, TestDescriptor, TestExpectation, TestKind, DEFAULT_AVAILABLE_GAS,
+};
+
+#[test]
+fn test_config_overlay() {
+    let base =
+        TestConfig::builder().expect_panic(PanicExpectation::Exact(vec![Felt252::from(7)])).build();
+
+    let overlaid = base.clone().overlay(PartialTestConfig {
+        gas_mode: Some(GasMode::Limited(1000)),
+        ..Default::default()
+    });
+    assert_eq!(overlaid.gas_mode, GasMode::Limited(1000));
+    assert_eq!(overlaid.expectation, base.expectation);
+    assert_eq!(overlaid, TestConfig { gas_mode: GasMode::Limited(1000), ..base.clone() });
+
+    let overlaid = base.clone().overlay(PartialTestConfig {
+        expectation: Some(TestExpectation::Success),
+        min_gas: Some(Some(10)),
+        ..Default::default()
+    });
+    assert_eq!(overlaid.gas_mode, base.gas_mode);
+    assert_eq!(overlaid.expectation, TestExpectation::Success);
+    assert_eq!(overlaid.min_gas, Some(10));
+
+    assert_eq!(base.clone().overlay(PartialTestConfig::default()), base);
+}
+
+#[test]
+fn test_config_fingerprint() {
+    let config = TestConfig::builder()
+        .available_gas(Some(1000))
+        .expect_panic(PanicExpectation::Exact(vec![Felt252::from(7)]))
+        .build();
+    // A config read back from its serialization, as in a later run, has the same fingerprint.
+    let serialized = serde_json::to_string(&config).unwrap();
+    let deserialized = TestConfig::from_serialized(&serialized).unwrap();
+    assert_eq!(deserialized.config_fingerprint(), config.config_fingerprint());
+    // A newly created hasher is used for every fingerprint, so it does not depend on the state.
+    assert_eq!(config.config_fingerprint(), config.clone().config_fingerprint());
+    // The hash algorithm is fixed, so the fingerprint is pinned across builds and platforms.
+    assert_eq!(config.config_fingerprint(), 17868727395350818017);
+
+    // The location of the test does not affect its fingerprint.
+    let moved = TestConfig {
+        definition_span: Some(TestDefinitionSpan { file: "lib.cairo".into(), line: 3, col: 4 }),
+        ..config.clone()
+    };
+    assert_eq!(moved.config_fingerprint(), config.config_fingerprint());
+
+    let changed = TestConfig { expectation: TestExpectation::Success, ..config.clone() };
+    assert_ne!(changed.config_fingerprint(), config.config_fingerprint());
+    let changed = TestConfig { gas_budget: Some(500), ..config.clone() };
+    assert_ne!(changed.config_fingerprint(), config.config_fingerprint());
+}
+
+#[test]
+fn test_config_try_new() {
+    let config = TestConfig::try_new(PartialTestConfig {
+        gas_mode: Some(GasMode::Limited(100)),
+        min_gas: Some(Some(10)),
+        max_gas: Some(Some(100)),
+        ..Default::default()
+    })
+    .unwrap();
+    assert_eq!((config.min_gas, config.max_gas), (Some(10), Some(100)));
+
+    let rejection =
+        |fields: PartialTestConfig| TestConfig::try_new(fields).unwrap_err().to_string();
+    assert_eq!(
+        rejection(PartialTestConfig {
+            gas_mode: Some(GasMode::Limited(10)),
+            min_gas: Some(Some(100)),
+            max_gas: Some(Some(10)),
+            ..Default::default()
+        }),
+        "Minimal gas 100 is greater than maximal gas 10."
+    );
+    assert_eq!(
+        rejection(PartialTestConfig {
+            gas_mode: Some(GasMode::Static),
+            min_gas: Some(Some(10)),
+            ..Default::default()
+        }),
+        "Gas bounds require the test to run with limited gas."
+    );
+    assert_eq!(
+        rejection(PartialTestConfig {
+            gas_mode: Some(GasMode::Limited(50)),
+            max_gas: Some(Some(100)),
+            ..Default::default()
+        }),
+        "The test runs with 50 gas, instead of its maximal gas 100."
+    );
+    assert_eq!(
+        rejection(PartialTestConfig {
+            kind: Some(TestKind::Bench),
+            expectation: Some(TestExpectation::Panics(PanicExpectation::Any)),
+            ..Default::default()
+        }),
+        "A benchmark cannot expect a panic."
+    );
+    assert_eq!(
+        rejection(PartialTestConfig {
+            ignore_reason: Some(Some("flaky".into())),
+            ..Default::default()
+        }),
+        "An ignore reason is set for a test that is not ignored."
+    );
+    assert_eq!(
+        rejection(PartialTestConfig { timeout_ms: Some(Some(0)), ..Default::default() }),
+        "The timeout must be positive."
+    );
+    assert_eq!(
+        rejection(PartialTestConfig { required_cfg: Some(Some("".into())), ..Default::default() }),
+        "The required feature must not be empty."
+    );
+    assert_eq!(
+        rejection(PartialTestConfig { tags: Some(vec!["".into()]), ..Default::default() }),
+        "Tags must not be empty."
+    );
+    assert_eq!(
+        rejection(PartialTestConfig {
+            tags: Some(vec!["slow".into(), "slow".into()]),
+            ..Default::default()
+        }),
+        "Tags must not be repeated."
+    );
+}
+
+/// Parses `code`, which must start with a free function, and returns the function's attributes.
+fn parse_function_attributes(db: &SimpleParserDatabase, code: &str) -> Vec<Attribute> {
+    let file_id = FileId::new(db, PathBuf::from("test.cairo"));
+    let (syntax_file, _) = get_syntax_file_and_diagnostics(db, file_id, code);
+    let ast::Item::FreeFunction(func) = &syntax_file.items(db).elements(db)[0] else {
+        panic!("Expected a free function.");
+    };
+    func.attributes(db).structurize(db)
+}
+
+#[test]
+fn test_ignored_should_panic_warning() {
+    let db = SimpleParserDatabase::default();
+    let attrs =
+        parse_function_attributes(&db, "#[test]\n#[ignore]\n#[should_panic]\nfn foo() {}\n");
+
+    // The combination is legal, so the configuration is still extracted.
+    assert!(matches!(
+        try_extract_test_config(&db, attrs.clone(), Default::default()),
+        Ok(ExtractedTestConfig::Test(_))
+    ));
+    let warnings = test_config_warnings(&attrs);
+    assert_eq!(
+        warnings.iter().map(|diagnostic| diagnostic.message.as_str()).collect_vec(),
+        ["Attribute `should_panic` has no effect, as the test is ignored by `ignore`."]
+    );
+}
+
+#[test]
+fn test_default_gas_lint() {
+    let db = SimpleParserDatabase::default();
+    let attrs =
+        parse_function_attributes(&db, "#[test]\n#[available_gas(4294967295)]\nfn foo() {}\n");
+
+    // The gas is legal, so the configuration is still extracted.
+    assert!(matches!(
+        try_extract_test_config(&db, attrs.clone(), Default::default()),
+        Ok(ExtractedTestConfig::Test(_))
+    ));
+    assert_eq!(
+        test_config_lints(&db, &attrs)
+            .iter()
+            .map(|diagnostic| diagnostic.message.as_str())
+            .collect_vec(),
+        [
+            "Explicit gas equals the unbounded default, 4294967295; consider `static` if \
+             unbounded gas is intended."
+        ]
+    );
+
+    for attr in ["#[available_gas(4294967294)]", "#[available_gas(static)]", ""] {
+        let attrs = parse_function_attributes(&db, &format!("#[test]\n{attr}\nfn foo() {{}}\n"));
+        assert!(test_config_lints(&db, &attrs).is_empty());
+    }
+}
+
+#[test]
+fn test_panic_reason() {
+    let db = SimpleParserDatabase::default();
+    let attrs = parse_function_attributes(
+        &db,
+        "#[test]\n#[should_panic(expected: (1,), reason: \"documented invariant\")]\nfn foo() {}\n",
+    );
+    let Ok(ExtractedTestConfig::Test(config)) =
+        try_extract_test_config(&db, attrs, Default::default())
+    else {
+        panic!("Expected a test.");
+    };
+    assert_eq!(
+        config.expectation,
+        TestExpectation::Panics(PanicExpectation::Exact(vec![Felt252::from(1)]))
+    );
+    assert_eq!(config.panic_reason.as_deref(), Some("documented invariant"));
+    let serialized = serde_json::to_string(&config).unwrap();
+    assert_eq!(TestConfig::from_serialized(&serialized).unwrap(), config);
+
+    let attrs = parse_function_attributes(
+        &db,
+        "#[test]\n#[should_panic(reason: \"documented invariant\")]\nfn foo() {}\n",
+    );
+    let Ok(ExtractedTestConfig::Test(config)) =
+        try_extract_test_config(&db, attrs, Default::default())
+    else {
+        panic!("Expected a test.");
+    };
+    assert_eq!(config.expectation, TestExpectation::Panics(PanicExpectation::Any));
+    assert_eq!(config.panic_reason.as_deref(), Some("documented invariant"));
+
+    let attrs = parse_function_attributes(
+        &db,
+        "#[test]\n#[should_panic(expected: (1,), because: \"invariant\")]\nfn foo() {}\n",
+    );
+    let diagnostics = try_extract_test_config(&db, attrs, Default::default()).unwrap_err();
+    assert_eq!(
+        diagnostics.iter().map(|diagnostic| diagnostic.message.as_str()).collect_vec(),
+        [
+            "Unknown argument `because`, expected `expected`, `expected_contains`, `expected_any` \
+             or `reason`."
+        ]
+    );
+
+    let attrs = parse_function_attributes(
+        &db,
+        "#[test]\n#[should_panic(expected: (1,), reason: 1)]\nfn foo() {}\n",
+    );
+    let (config, diagnostics) = try_extract_test_config_lenient(&db, attrs, Default::default());
+    assert_eq!(config.unwrap().panic_reason, None);
+    assert_eq!(
+        diagnostics.iter().map(|diagnostic| diagnostic.message.as_str()).collect_vec(),
+        ["`reason` must be a non-empty string."]
+    );
+
+    assert_eq!(
+        TestConfig::try_new(PartialTestConfig {
+            panic_reason: Some(Some("documented invariant".into())),
+            ..Default::default()
+        })
+        .unwrap_err()
+        .to_string(),
+        "A panic reason is set for a test that does not expect a panic."
+    );
+}
+
+#[test]
+fn test_positional_panic_expectation() {
+    let db = SimpleParserDatabase::default();
+    let attrs = parse_function_attributes(&db, "#[test]\n#[should_panic((1,))]\nfn foo() {}\n");
+    let diagnostics = try_extract_test_config(&db, attrs, Default::default()).unwrap_err();
+    assert_eq!(
+        diagnostics.iter().map(|diagnostic| diagnostic.message.as_str()).collect_vec(),
+        ["Expected panic values must be passed by name, as in `expected: (1,)`."]
+    );
+}
+
+#[test]
+fn test_enum_variant_panic_expectation() {
+    let db = SimpleParserDatabase::default();
+    let enum_code = "enum MyError {\n    Underflow,\n    Overflow: (),\n    Custom: felt252,\n}\n";
+    let attrs = parse_function_attributes(
+        &db,
+        &format!(
+            "#[test]\n#[should_panic(expected: MyError::Overflow)]\nfn foo() {{}}\n{enum_code}"
+        ),
+    );
+    let Ok(ExtractedTestConfig::Test(config)) =
+        try_extract_test_config(&db, attrs, Default::default())
+    else {
+        panic!("Expected a test.");
+    };
+    assert_eq!(
+        config.expectation,
+        TestExpectation::Panics(PanicExpectation::Exact(vec![Felt252::from(1)]))
+    );
+
+    for path in ["MyError::Custom", "MyError::Missing", "OtherError::Overflow"] {
+        let attrs = parse_function_attributes(
+            &db,
+            &format!("#[test]\n#[should_panic(expected: {path})]\nfn foo() {{}}\n{enum_code}"),
+        );
+        let diagnostics = try_extract_test_config(&db, attrs, Default::default()).unwrap_err();
+        assert_eq!(
+            diagnostics.iter().map(|diagnostic| diagnostic.message.clone()).collect_vec(),
+            [format!(
+                "`{path}` is not a variant without data of an enum defined in the module of the \
+                 test."
+            )]
+        );
+    }
+}
+
+#[test]
+fn test_empty_panic_expectation() {
+    let db = SimpleParserDatabase::default();
+    let attrs =
+        parse_function_attributes(&db, "#[test]\n#[should_panic(expected: ())]\nfn foo() {}\n");
+    let diagnostics = try_extract_test_config(&db, attrs, Default::default()).unwrap_err();
+    assert_eq!(
+        diagnostics.iter().map(|diagnostic| diagnostic.message.as_str()).collect_vec(),
+        [
+            "Expected panic is empty, and would only match a panic without values. Remove \
+             `expected` to accept any panic, provide the expected values, or use `expected: \
+             empty` to accept only an empty panic."]
+    );
+
+    let attrs =
+        parse_function_attributes(&db, "#[test]\n#[should_panic(expected: empty)]\nfn foo() {}\n");
+    let Ok(ExtractedTestConfig::Test(config)) =
+        try_extract_test_config(&db, attrs, Default::default())
+    else {
+        panic!("Expected a test.");
+    };
+    assert_eq!(config.expectation, TestExpectation::Panics(PanicExpectation::Exact(vec![])));
+}
+
+#[test]
+fn test_tags() {
+    let db = SimpleParserDatabase::default();
+    let attrs = parse_function_attributes(
+        &db,
+        "#[test]\n#[test_tag(\"slow\")]\n#[test_tag(\"io\")]\n#[test_tag(\"slow\")]\nfn foo() {}\n",
+    );
+    let Ok(ExtractedTestConfig::Test(config)) =
+        try_extract_test_config(&db, attrs, Default::default())
+    else {
+        panic!("Expected a test.");
+    };
+    assert_eq!(config.tags, ["slow", "io"]);
+
+    let attrs = parse_function_attributes(&db, "#[test]\n#[test_tag(slow)]\nfn foo() {}\n");
+    let diagnostics = try_extract_test_config(&db, attrs, Default::default()).unwrap_err();
+    assert_eq!(
+        diagnostics.iter().map(|diagnostic| diagnostic.message.as_str()).collect_vec(),
+        ["Attribute should have a single non-empty string argument."]
+    );
+
+    let attrs = parse_function_attributes(&db, "#[test_tag(\"slow\")]\nfn foo() {}\n");
+    let diagnostics = try_extract_test_config(&db, attrs, Default::default()).unwrap_err();
+    assert_eq!(
+        diagnostics.iter().map(|diagnostic| diagnostic.message.as_str()).collect_vec(),
+        ["Attribute should only appear on tests."]
+    );
+}
+
+#[test]
+fn test_gas_report() {
+    let db = SimpleParserDatabase::default();
+    let attrs =
+        parse_function_attributes(&db, "#[test]\n#[gas_report(expected: 1234)]\nfn foo() {}\n");
+    let Ok(ExtractedTestConfig::Test(config)) =
+        try_extract_test_config(&db, attrs, Default::default())
+    else {
+        panic!("Expected a test.");
+    };
+    assert_eq!(config.expected_gas_report, Some(1234));
+    let serialized = serde_json::to_string(&config).unwrap();
+    assert_eq!(TestConfig::from_serialized(&serialized).unwrap(), config);
+
+    let attrs = parse_function_attributes(&db, "#[test]\n#[gas_report(1234)]\nfn foo() {}\n");
+    let diagnostics = try_extract_test_config(&db, attrs, Default::default()).unwrap_err();
+    assert_eq!(
+        diagnostics.iter().map(|diagnostic| diagnostic.message.as_str()).collect_vec(),
+        ["Attribute should have a single `expected: <number>` argument, the expected gas usage."]
+    );
+}
+
+#[test]
+fn test_gas_budget() {
+    let db = SimpleParserDatabase::default();
+    let attrs = parse_function_attributes(
+        &db,
+        "#[test]\n#[available_gas(2000)]\n#[max_gas(1000)]\nfn foo() {}\n",
+    );
+    let Ok(ExtractedTestConfig::Test(config)) =
+        try_extract_test_config(&db, attrs, Default::default())
+    else {
+        panic!("Expected a test.");
+    };
+    assert_eq!(config.gas_budget, Some(1000));
+    assert_eq!(config.gas_mode, GasMode::Limited(2000));
+    let serialized = serde_json::to_string(&config).unwrap();
+    assert_eq!(TestConfig::from_serialized(&serialized).unwrap(), config);
+
+    let attrs = parse_function_attributes(
+        &db,
+        "#[test]\n#[available_gas(500)]\n#[max_gas(1000)]\nfn foo() {}\n",
+    );
+    let diagnostics = try_extract_test_config(&db, attrs, Default::default()).unwrap_err();
+    assert_eq!(
+        diagnostics.iter().map(|diagnostic| diagnostic.message.as_str()).collect_vec(),
+        ["Gas budget 1000 is greater than the available gas 500."]
+    );
+
+    let attrs = parse_function_attributes(&db, "#[test]\n#[max_gas(\"a\")]\nfn foo() {}\n");
+    let diagnostics = try_extract_test_config(&db, attrs, Default::default()).unwrap_err();
+    assert_eq!(
+        diagnostics.iter().map(|diagnostic| diagnostic.message.as_str()).collect_vec(),
+        ["Attribute should have a single numeric literal argument, the gas budget."]
+    );
+
+    assert_eq!(
+        TestConfig::try_new(PartialTestConfig {
+            gas_mode: Some(GasMode::Limited(500)),
+            gas_budget: Some(Some(1000)),
+            ..Default::default()
+        })
+        .unwrap_err()
+        .to_string(),
+        "Gas budget 1000 is greater than the available gas 500."
+    );
+}
+
+#[test]
+fn test_isolated() {
+    let db = SimpleParserDatabase::default();
+    let attrs = parse_function_attributes(&db, "#[test]\n#[test_isolated]\nfn foo() {}\n");
+    let Ok(ExtractedTestConfig::Test(config)) =
+        try_extract_test_config(&db, attrs, Default::default())
+    else {
+        panic!("Expected a test.");
+    };
+    assert!(config.isolated);
+    let serialized = serde_json::to_string(&config).unwrap();
+    assert_eq!(TestConfig::from_serialized(&serialized).unwrap(), config);
+
+    let attrs = parse_function_attributes(&db, "#[test]\nfn foo() {}\n");
+    let Ok(ExtractedTestConfig::Test(config)) =
+        try_extract_test_config(&db, attrs, Default::default())
+    else {
+        panic!("Expected a test.");
+    };
+    assert!(!config.isolated);
+
+    let attrs =
+        parse_function_attributes(&db, "#[test]\n#[test_isolated(worker: 1)]\nfn foo() {}\n");
+    let diagnostics = try_extract_test_config(&db, attrs, Default::default()).unwrap_err();
+    assert_eq!(
+        diagnostics.iter().map(|diagnostic| diagnostic.message.as_str()).collect_vec(),
+        ["Attribute should not have arguments."]
+    );
+
+    let attrs = parse_function_attributes(&db, "#[test_isolated]\nfn foo() {}\n");
+    let diagnostics = try_extract_test_config(&db, attrs, Default::default()).unwrap_err();
+    assert_eq!(
+        diagnostics.iter().map(|diagnostic| diagnostic.message.as_str()).collect_vec(),
+        ["Attribute should only appear on tests."]
+    );
+}
+
+#[test]
+fn test_expected_steps() {
+    let db = SimpleParserDatabase::default();
+    let attrs =
+        parse_function_attributes(&db, "#[test]\n#[expected_steps(max: 500)]\nfn foo() {}\n");
+    let Ok(ExtractedTestConfig::Test(config)) =
+        try_extract_test_config(&db, attrs, Default::default())
+    else {
+        panic!("Expected a test.");
+    };
+    assert_eq!(config.max_steps, Some(500));
+    let serialized = serde_json::to_string(&config).unwrap();
+    assert_eq!(TestConfig::from_serialized(&serialized).unwrap(), config);
+
+    for malformed in ["#[expected_steps(500)]", "#[expected_steps(max: 0)]", "#[expected_steps]"] {
+        let attrs =
+            parse_function_attributes(&db, &format!("#[test]\n{malformed}\nfn foo() {{}}\n"));
+        let diagnostics = try_extract_test_config(&db, attrs, Default::default()).unwrap_err();
+        assert_eq!(
+            diagnostics.iter().map(|diagnostic| diagnostic.message.as_str()).collect_vec(),
+            [
+                "Attribute should have a single `max: <positive number>` argument, the maximal \
+                 number of steps."
+            ]
+        );
+    }
+
+    let attrs = parse_function_attributes(&db, "#[expected_steps(max: 500)]\nfn foo() {}\n");
+    let diagnostics = try_extract_test_config(&db, attrs, Default::default()).unwrap_err();
+    assert_eq!(
+        diagnostics.iter().map(|diagnostic| diagnostic.message.as_str()).collect_vec(),
+        ["Attribute should only appear on tests."]
+    );
+}
+
+#[test]
+fn test_run_after() {
+    let db = SimpleParserDatabase::default();
+    let attrs = parse_function_attributes(
+        &db,
+        "#[test]\n#[test_after(\"setup_db\")]\n#[test_after(\"setup_cache\")]\n#[test_after(\"\
+         setup_db\")]\nfn foo() {}\n",
+    );
+    let Ok(ExtractedTestConfig::Test(config)) =
+        try_extract_test_config(&db, attrs, Default::default())
+    else {
+        panic!("Expected a test.");
+    };
+    assert_eq!(config.run_after, ["setup_db", "setup_cache"]);
+    let serialized = serde_json::to_string(&config).unwrap();
+    assert_eq!(TestConfig::from_serialized(&serialized).unwrap(), config);
+
+    let attrs = parse_function_attributes(&db, "#[test]\n#[test_after(setup_db)]\nfn foo() {}\n");
+    let diagnostics = try_extract_test_config(&db, attrs, Default::default()).unwrap_err();
+    assert_eq!(
+        diagnostics.iter().map(|diagnostic| diagnostic.message.as_str()).collect_vec(),
+        ["Attribute should have a single non-empty string argument."]
+    );
+
+    let attrs = parse_function_attributes(&db, "#[test_after(\"setup_db\")]\nfn foo() {}\n");
+    let diagnostics = try_extract_test_config(&db, attrs, Default::default()).unwrap_err();
+    assert_eq!(
+        diagnostics.iter().map(|diagnostic| diagnostic.message.as_str()).collect_vec(),
+        ["Attribute should only appear on tests."]
+    );
+
+    assert_eq!(
+        TestConfig::try_new(PartialTestConfig {
+            run_after: Some(vec!["setup_db".into(), "setup_db".into()]),
+            ..Default::default()
+        })
+        .unwrap_err()
+        .to_string(),
+        "The names of the tests to run after must not be repeated."
+    );
+}
+
+#[test]
+fn test_exact_gas() {
+    let db = SimpleParserDatabase::default();
+    let attrs = parse_function_attributes(&db, "#[test]\n#[exact_gas(1234)]\nfn foo() {}\n");
+    let Ok(ExtractedTestConfig::Test(config)) = try_extract_test_config(
+        &db,
+        attrs,
+        TestConfigExtractionOptions { default_gas: None, ..Default::default() },
+    ) else {
+        panic!("Expected a test.");
+    };
+    assert_eq!(config.exact_gas, Some(1234));
+    assert_eq!(config.gas_mode, GasMode::Limited(DEFAULT_AVAILABLE_GAS));
+    let serialized = serde_json::to_string(&config).unwrap();
+    assert_eq!(TestConfig::from_serialized(&serialized).unwrap(), config);
+
+    let attrs = parse_function_attributes(&db, "#[test]\n#[exact_gas(\"1234\")]\nfn foo() {}\n");
+    let diagnostics = try_extract_test_config(&db, attrs, Default::default()).unwrap_err();
+    assert_eq!(
+        diagnostics.iter().map(|diagnostic| diagnostic.message.as_str()).collect_vec(),
+        ["Attribute should have a single numeric literal argument, the exact gas consumption."]
+    );
+
+    for other_attr in ["available_gas(2000)", "available_gas(min: 10, max: 2000)", "max_gas(2000)"]
+    {
+        let attrs = parse_function_attributes(
+            &db,
+            &format!("#[test]\n#[exact_gas(1234)]\n#[{other_attr}]\nfn foo() {{}}\n"),
+        );
+        let (config, diagnostics) = try_extract_test_config_lenient(&db, attrs, Default::default());
+        assert_eq!(config.unwrap().exact_gas, None);
+        let other_attr_name = other_attr.split('(').next().unwrap();
+        assert_eq!(
+            diagnostics.iter().map(|diagnostic| diagnostic.message.clone()).collect_vec(),
+            [format!("Attribute cannot appear together with `{other_attr_name}`.")]
+        );
+    }
+
+    assert_eq!(
+        TestConfig::try_new(PartialTestConfig {
+            gas_mode: Some(GasMode::Unreported),
+            exact_gas: Some(Some(1234)),
+            ..Default::default()
+        })
+        .unwrap_err()
+        .to_string(),
+        "Exact gas requires the test to run with limited gas."
+    );
+    assert_eq!(
+        TestConfig::try_new(PartialTestConfig {
+            gas_budget: Some(Some(2000)),
+            exact_gas: Some(Some(1234)),
+            ..Default::default()
+        })
+        .unwrap_err()
+        .to_string(),
+        "Exact gas cannot be combined with gas bounds or a gas budget."
+    );
+}
+
+#[test]
+fn test_long_short_string_panic_expectation() {
+    let db = SimpleParserDatabase::default();
+    let attrs = parse_function_attributes(
+        &db,
+        "#[test]\n#[should_panic(expected: ('0123456789012345678901234567890123456789',))]\nfn \
+         foo() {}\n",
+    );
+    let diagnostics = try_extract_test_config(&db, attrs, Default::default()).unwrap_err();
+    assert_eq!(
+        diagnostics.iter().map(|diagnostic| diagnostic.message.as_str()).collect_vec(),
+        [
+            "Short string `'0123456789012345678901234567890123456789'` is 40 bytes long, \
+             exceeding the 31 bytes of a felt252. Use a string of the form `expected: \
+             \"<string>\"` for longer messages."]
+    );
+}
+
+#[test]
+fn test_test_case_args() {
+    let db = SimpleParserDatabase::default();
+    let attrs = parse_function_attributes(
+        &db,
+        "#[test]\n#[test_case(1, -1, 'a')]\nfn foo(a: felt252, b: felt252, c: felt252) {}\n",
+    );
+    let configs = try_extract_test_configs(&db, attrs, Default::default()).unwrap();
+    assert_eq!(
+        configs.iter().map(|config| config.args.clone()).collect_vec(),
+        [vec![Felt252::from(1), Felt252::from(-1), Felt252::from(97)]]
+    );
+
+    // A literal out of the felt252 range is reported rather than silently replaced by zero.
+    let attrs = parse_function_attributes(
+        &db,
+        "#[test]\n#[test_case(0x800000000000011000000000000000000000000000000000000000000000001)]\n\
+         fn foo(a: felt252) {}\n",
+    );
+    let diagnostics = try_extract_test_configs(&db, attrs, Default::default()).unwrap_err();
+    assert_eq!(
+        diagnostics.iter().map(|diagnostic| diagnostic.message.as_str()).collect_vec(),
+        [
+            "Test case argument \
+             `0x800000000000011000000000000000000000000000000000000000000000001` is not a valid \
+             felt252 literal."]
+    );
+}
+
+#[test]
+fn test_zero_available_gas() {
+    let db = SimpleParserDatabase::default();
+    let attrs = parse_function_attributes(&db, "#[test]\n#[available_gas(0)]\nfn foo() {}\n");
+    let Ok(ExtractedTestConfig::Test(config)) =
+        try_extract_test_config(&db, attrs, Default::default())
+    else {
+        panic!("Expected a test.");
+    };
+    assert_eq!(config.gas_mode, GasMode::Limited(0));
+    assert_eq!(config.available_gas(), Some(0));
+
+    // Without the attribute, the test runs with the default gas rather than zero gas.
+    let attrs = parse_function_attributes(&db, "#[test]\nfn foo() {}\n");
+    let Ok(ExtractedTestConfig::Test(config)) =
+        try_extract_test_config(&db, attrs, Default::default())
+    else {
+        panic!("Expected a test.");
+    };
+    assert_eq!(config.gas_mode, GasMode::Limited(u32::MAX as usize));
+}
+
+#[test]
+fn test_default_gas() {
+    let db = SimpleParserDatabase::default();
+    let attrs = parse_function_attributes(&db, "#[test]\nfn foo() {}\n");
+    let Ok(ExtractedTestConfig::Test(config)) = try_extract_test_config(
+        &db,
+        attrs.clone(),
+        TestConfigExtractionOptions { default_gas: None, ..Default::default() },
+    ) else {
+        panic!("Expected a test.");
+    };
+    assert_eq!(config.gas_mode, GasMode::Static);
+    let Ok(ExtractedTestConfig::Test(config)) = try_extract_test_config(
+        &db,
+        attrs,
+        TestConfigExtractionOptions { default_gas: Some(1000), ..Default::default() },
+    ) else {
+        panic!("Expected a test.");
+    };
+    assert_eq!(config.gas_mode, GasMode::Limited(1000));
+
+    // An explicit gas attribute is not affected by the default.
+    let attrs = parse_function_attributes(&db, "#[test]\n#[available_gas(50)]\nfn foo() {}\n");
+    let Ok(ExtractedTestConfig::Test(config)) = try_extract_test_config(
+        &db,
+        attrs,
+        TestConfigExtractionOptions { default_gas: None, ..Default::default() },
+    ) else {
+        panic!("Expected a test.");
+    };
+    assert_eq!(config.gas_mode, GasMode::Limited(50));
+}
+
+#[test]
+fn test_entry_name() {
+    let db = SimpleParserDatabase::default();
+    let attrs = parse_function_attributes(
+        &db,
+        "#[test]\n#[test_name(\"my_suite::case_1\")]\nfn foo() {}\n",
+    );
+    let Ok(ExtractedTestConfig::Test(config)) =
+        try_extract_test_config(&db, attrs, Default::default())
+    else {
+        panic!("Expected a test.");
+    };
+    assert_eq!(config.entry_name.as_deref(), Some("my_suite::case_1"));
+
+    let attrs = parse_function_attributes(
+        &db,
+        "#[test]\n#[test_name(\"\")]\n#[test_name(\"b\")]\nfn foo() {}\n",
+    );
+    let diagnostics = try_extract_test_config(&db, attrs, Default::default()).unwrap_err();
+    assert_eq!(
+        diagnostics.iter().map(|diagnostic| diagnostic.message.as_str()).collect_vec(),
+        [
+            "Duplicate `test_name` attribute.",
+            "Attribute should have a single non-empty string argument."
+        ]
+    );
+}
+
+#[test]
+fn test_lenient_extraction() {
+    let db = SimpleParserDatabase::default();
+    // A malformed gas attribute is recoverable, and the test runs with the default gas.
+    let attrs = parse_function_attributes(
+        &db,
+        "#[test]\n#[available_gas(\"x\")]\n#[should_panic]\nfn foo() {}\n",
+    );
+    assert!(try_extract_test_config(&db, attrs.clone(), Default::default()).is_err());
+    let (config, diagnostics) = try_extract_test_config_lenient(&db, attrs, Default::default());
+    let config = config.unwrap();
+    assert_eq!(config.gas_mode, TestConfig::builder().build().gas_mode);
+    assert_eq!(config.expectation, TestExpectation::Panics(PanicExpectation::Any));
+    assert_eq!(
+        diagnostics.iter().map(|diagnostic| diagnostic.message.as_str()).collect_vec(),
+        ["Attribute should have a single numeric literal argument or `static`."]
+    );
+
+    // A malformed panic expectation is fatal.
+    let attrs =
+        parse_function_attributes(&db, "#[test]\n#[should_panic(expected: 5)]\nfn foo() {}\n");
+    let (config, diagnostics) = try_extract_test_config_lenient(&db, attrs, Default::default());
+    assert_eq!(config, None);
+    assert_eq!(diagnostics.len(), 1);
+}
+
+#[test]
+fn test_descriptor() {
+    let config = TestConfig::builder()
+        .available_gas(Some(1000))
+        .expect_panic(PanicExpectation::Exact(vec![Felt252::from(1), Felt252::from(-1)]))
+        .build();
+    let descriptor = TestDescriptor::from(&config);
+    assert_eq!(
+        serde_json::to_string(&descriptor).unwrap(),
+        "{\"available_gas\":1000,\"should_panic\":true,\"expected_panic\":[\"1\",\
+         \"3618502788666131213697322783095070105623107215331596699973092056135872020480\"],\
+         \"ignored\":false}"
+    );
+
+    let descriptor = TestDescriptor::from(&TestConfig::builder().available_gas(None).build());
+    assert_eq!(
+        descriptor,
+        TestDescriptor {
+            available_gas: None,
+            should_panic: false,
+            expected_panic: None,
+            ignored: false,
+        }
+    );
+}
+
+#[test]
+fn test_expectation_display() {
+    let felts = |values: &[u64]| values.iter().map(|value| Felt252::from(*value)).collect_vec();
+    assert_eq!(TestExpectation::Success.to_string(), "expects success");
+    assert_eq!(TestExpectation::ReturnsValue(felts(&[7])).to_string(), "expects result: [7]");
+    assert_eq!(TestExpectation::Panics(PanicExpectation::Any).to_string(), "expects panic (any)");
+    assert_eq!(
+        TestExpectation::Panics(PanicExpectation::Exact(felts(&[1, 2]))).to_string(),
+        "expects panic: [1, 2]"
+    );
+    assert_eq!(PanicExpectation::Any.to_string(), "any");
+    assert_eq!(PanicExpectation::Exact(felts(&[1, 2])).to_string(), "[1, 2]");
+    assert_eq!(PanicExpectation::Contains(felts(&[3])).to_string(), "containing [3]");
+    assert_eq!(PanicExpectation::OutOfGas.to_string(), "out of gas");
+    assert_eq!(
+        PanicExpectation::AnyOf(vec![felts(&[1]), felts(&[2, 3])]).to_string(),
+        "any of [[1], [2, 3]]"
+    );
+}
+
+#[test]
+fn test_encode_byte_array_panic() {
+    let magic = Felt252::parse_bytes(
+        b"46a6158a16a947e5916b2a2ca68501a45e93d7110e81aa2d6438b1c57c879a3",
+        16,
+    )
+    .unwrap();
+    assert_eq!(
+        encode_byte_array_panic("hello"),
+        [magic.clone(), Felt252::from(0), Felt252::from_bytes_be(b"hello"), Felt252::from(5)]
+    );
+    // A message of a full word and a pending word.
+    assert_eq!(
+        encode_byte_array_panic("A message that is longer than a single full word."),
+        [
+            magic.clone(),
+            Felt252::from(1),
+            Felt252::from_bytes_be(b"A message that is longer than a"),
+            Felt252::from_bytes_be(b" single full word."),
+            Felt252::from(18),
+        ]
+    );
+    // A message of exactly a full word has an empty pending word.
+    assert_eq!(
+        encode_byte_array_panic("A message of a single full word"),
+        [
+            magic,
+            Felt252::from(1),
+            Felt252::from_bytes_be(b"A message of a single full word"),
+            Felt252::from(0),
+            Felt252::from(0),
+        ]
+    );
+}
@@ -6,10 +6,13 @@ use cairo_felt::Felt252;
 use cairo_lang_compiler::db::RootDatabase;
 use cairo_lang_compiler::diagnostics::DiagnosticsReporter;
 use cairo_lang_compiler::project::setup_project;
+use cairo_lang_defs::plugin::PluginDiagnostic;
 use cairo_lang_filesystem::cfg::{Cfg, CfgSet};
 use cairo_lang_filesystem::ids::CrateId;
 use cairo_lang_runner::short_string::as_cairo_short_string;
-use cairo_lang_runner::{RunResultValue, SierraCasmRunner};
+use cairo_lang_runner::{
+    Arg, RunResultStarknet, RunResultValue, RunnerError, SierraCasmRunner, StarknetState,
+};
 use cairo_lang_sierra::extensions::gas::CostTokenType;
 use cairo_lang_sierra::ids::FunctionId;
 use cairo_lang_sierra::program::Program;
@@ -17,8 +20,11 @@ use cairo_lang_sierra_to_casm::metadata::MetadataComputationConfig;
 use cairo_lang_starknet::contract::ContractInfo;
 use cairo_lang_starknet::inline_macros::selector::SelectorMacro;
 use cairo_lang_starknet::plugin::StarkNetPlugin;
-use cairo_lang_test_plugin::test_config::{PanicExpectation, TestExpectation};
-use cairo_lang_test_plugin::{compile_test_prepared_db, TestCompilation, TestConfig, TestPlugin};
+use cairo_lang_syntax::node::db::SyntaxGroup;
+use cairo_lang_test_plugin::test_config::{GasMode, OUT_OF_GAS_PANIC_MESSAGE};
+use cairo_lang_test_plugin::{
+    compile_test_prepared_db, test_function_name, TestCompilation, TestConfig, TestPlugin,
+};
 use cairo_lang_utils::casts::IntoOrPanic;
 use cairo_lang_utils::ordered_hash_map::OrderedHashMap;
 use colored::Colorize;
@@ -52,11 +58,35 @@ impl TestRunner {
 
     /// Runs the tests and process the results for a summary.
     pub fn run(&self) -> Result<Option<TestsSummary>> {
-        let runner = CompiledTestRunner::new(self.compiler.build()?, self.config.clone());
+        let compiled = self.compiler.build()?;
+        for warning in &compiled.warnings {
+            print_warning(&self.compiler.db, warning);
+        }
+        let runner = CompiledTestRunner::new(compiled, self.config.clone());
         runner.run()
     }
 }
 
+/// Prints a warning-level diagnostic of the test attributes, with its location if it is known.
+fn print_warning(db: &dyn SyntaxGroup, warning: &PluginDiagnostic) {
+    let file_id = warning.stable_ptr.file_id(db);
+    let position = warning
+        .stable_ptr
+        .lookup(db)
+        .span_start_without_trivia(db)
+        .position_in_file(db.upcast(), file_id);
+    match position {
+        Some(position) => eprintln!(
+            "warning: {}\n --> {}:{}:{}",
+            warning.message,
+            file_id.full_path(db.upcast()),
+            position.line + 1,
+            position.col + 1
+        ),
+        None => eprintln!("warning: {}", warning.message),
+    }
+}
+
 pub struct CompiledTestRunner {
     pub compiled: TestCompilation,
     pub config: TestRunConfig,
@@ -239,6 +269,8 @@ struct TestResult {
     status: TestStatus,
     /// The gas usage of the run if relevant.
     gas_usage: Option<i64>,
+    /// The gas usage the test is expected to have, for reporting, if given.
+    expected_gas_usage: Option<usize>,
 }
 
 /// Summary data of the ran tests.
@@ -275,42 +307,54 @@ pub fn run_tests(
             if test.ignored {
                 return Ok((name, None));
             }
-            let func = runner.find_function(name.as_str())?;
-            let result = runner
-                .run_function_with_starknet_context(
-                    func,
-                    &[],
-                    test.available_gas,
-                    Default::default(),
-                )
-                .with_context(|| format!("Failed to run the function `{}`.", name.as_str()))?;
+            let func = runner.find_function(test_function_name(name.as_str()))?;
+            let args = test.args.iter().cloned().map(Arg::Value).collect_vec();
+            let result = match runner.run_function_with_starknet_context(
+                func,
+                &args,
+                test.available_gas(),
+                Default::default(),
+            ) {
+                // A test without enough gas to be called, e.g. with `#[available_gas(0)]`, runs
+                // out of gas immediately, and only this test fails rather than the whole run.
+                Err(RunnerError::NotEnoughGasToCall) => RunResultStarknet {
+                    gas_counter: test.available_gas().map(Felt252::from),
+                    memory: vec![],
+                    value: RunResultValue::Panic(vec![Felt252::from_bytes_be(
+                        OUT_OF_GAS_PANIC_MESSAGE.as_bytes(),
+                    )]),
+                    starknet_state: StarknetState::default(),
+                },
+                result => result
+                    .with_context(|| format!("Failed to run the function `{}`.", name.as_str()))?,
+            };
             Ok((
                 name,
                 Some(TestResult {
                     status: match &result.value {
-                        RunResultValue::Success(_) => match test.expectation {
-                            TestExpectation::Success => TestStatus::Success,
-                            TestExpectation::Panics(_) => TestStatus::Fail(result.value),
-                        },
-                        RunResultValue::Panic(value) => match test.expectation {
-                            TestExpectation::Success => TestStatus::Fail(result.value),
-                            TestExpectation::Panics(panic_expectation) => match panic_expectation {
-                                PanicExpectation::Exact(expected) if value != &expected => {
-                                    TestStatus::Fail(result.value)
-                                }
-                                _ => TestStatus::Success,
-                            },
-                        },
+                        RunResultValue::Success(values)
+                            if test.expectation.matches_result(false, values) =>
+                        {
+                            TestStatus::Success
+                        }
+                        RunResultValue::Panic(values)
+                            if test.expectation.matches_result(true, values) =>
+                        {
+                            TestStatus::Success
+                        }
+                        _ => TestStatus::Fail(result.value),
                     },
                     gas_usage: test
-                        .available_gas
+                        .available_gas()
                         .zip(result.gas_counter)
                         .map(|(before, after)| {
                             before.into_or_panic::<i64>() - after.to_bigint().to_i64().unwrap()
                         })
                         .or_else(|| {
                             runner.initial_required_gas(func).map(|gas| gas.into_or_panic::<i64>())
-                        }),
+                        })
+                        .filter(|_| test.gas_mode != GasMode::Unreported),
+                    expected_gas_usage: test.expected_gas_report,
                 }),
             ))
         })
@@ -327,17 +371,29 @@ pub fn run_tests(
                 }
             };
             let summary = wrapped_summary.as_mut().unwrap();
-            let (res_type, status_str, gas_usage) = match status {
-                Some(TestResult { status: TestStatus::Success, gas_usage }) => {
-                    (&mut summary.passed, "ok".bright_green(), gas_usage)
+            let (res_type, status_str, gas_usage, expected_gas_usage) = match status {
+                Some(TestResult { status: TestStatus::Success, gas_usage, expected_gas_usage }) => {
+                    (&mut summary.passed, "ok".bright_green(), gas_usage, expected_gas_usage)
                 }
-                Some(TestResult { status: TestStatus::Fail(run_result), gas_usage }) => {
+                Some(TestResult {
+                    status: TestStatus::Fail(run_result),
+                    gas_usage,
+                    expected_gas_usage,
+                }) => {
                     summary.failed_run_results.push(run_result);
-                    (&mut summary.failed, "fail".bright_red(), gas_usage)
+                    (&mut summary.failed, "fail".bright_red(), gas_usage, expected_gas_usage)
                 }
-                None => (&mut summary.ignored, "ignored".bright_yellow(), None),
+                None => (&mut summary.ignored, "ignored".bright_yellow(), None, None),
             };
-            if let Some(gas_usage) = gas_usage {
+            if let (Some(gas_usage), Some(expected)) = (gas_usage, expected_gas_usage) {
+                // The difference is only reported, and does not fail the test. It is computed in
+                // `i128`, so an expected gas beyond the range of `i64` cannot overflow.
+                let delta = i128::from(gas_usage) - expected as i128;
+                println!(
+                    "test {name} ... {status_str} (gas usage est.: {gas_usage}, expected: \
+                     {expected}, delta: {delta:+})"
+                );
+            } else if let Some(gas_usage) = gas_usage {
                 println!("test {name} ... {status_str} (gas usage est.: {gas_usage})");
             } else {
                 println!("test {name} ... {status_str}");
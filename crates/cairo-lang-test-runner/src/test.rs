@@ -1,10 +1,13 @@
+use std::path::PathBuf;
+
+use cairo_lang_test_plugin::test_config::TEST_CONFIG_VERSION;
+use cairo_lang_test_plugin::TestConfig;
 use itertools::Itertools;
 
-use crate::{TestCompilation, TestCompiler};
+use crate::{run_tests, TestCompilation, TestCompiler};
 
 #[test]
 fn test_compiled_serialization() {
-    use std::path::PathBuf;
     let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_data");
 
     let compiler = TestCompiler::try_new(&path, true).unwrap();
@@ -20,3 +23,83 @@ fn test_compiled_serialization() {
         deserialized.contracts_info.values().collect_vec()
     );
 }
+
+#[test]
+fn test_entry_name_collision() {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_data/entry_name_collision");
+
+    // The test is named as a non-test function of the program, so it could not be found by name.
+    let compiler = TestCompiler::try_new(&path, false).unwrap();
+    assert_eq!(
+        compiler.build().err().unwrap().to_string(),
+        "Multiple functions in the program are named `entry_name_collision::helper`."
+    );
+}
+
+#[test]
+fn test_zero_available_gas_run() {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_data/zero_available_gas");
+    let compiled = TestCompiler::try_new(&path, false).unwrap().build().unwrap();
+
+    // The test without enough gas to be called fails on its own, as running out of gas, and the
+    // other tests still run.
+    let summary = run_tests(
+        compiled.named_tests,
+        compiled.sierra_program,
+        compiled.function_set_costs,
+        compiled.contracts_info,
+    )
+    .unwrap();
+    assert_eq!(
+        summary.passed.into_iter().sorted().collect_vec(),
+        ["zero_available_gas::test_out_of_gas", "zero_available_gas::test_with_default_gas"]
+    );
+    assert!(summary.failed.is_empty());
+}
+
+#[test]
+fn test_config_versioned_serialization() {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_data");
+
+    let compiler = TestCompiler::try_new(&path, true).unwrap();
+    let compiled = compiler.build().unwrap();
+    let (_, config) = &compiled.named_tests[0];
+    let serialized = serde_json::to_string(config).unwrap();
+    assert_eq!(&TestConfig::from_serialized(&serialized).unwrap(), config);
+
+    let mut unversioned: serde_json::Value = serde_json::from_str(&serialized).unwrap();
+    unversioned.as_object_mut().unwrap().remove("version");
+    let err = TestConfig::from_serialized(&unversioned.to_string()).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "Test config of version 0 is not supported, expected version \
+             {TEST_CONFIG_VERSION}. Recompile the tests to update it."
+        )
+    );
+    // A configuration of the previous version is rejected.
+    let mut previous: serde_json::Value = serde_json::from_str(&serialized).unwrap();
+    previous["version"] = (TEST_CONFIG_VERSION - 1).into();
+    let err = TestConfig::from_serialized(&previous.to_string()).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "Test config of version {} is not supported, expected version \
+             {TEST_CONFIG_VERSION}. Recompile the tests to update it.",
+            TEST_CONFIG_VERSION - 1
+        )
+    );
+}
+
+#[test]
+fn test_config_definition_span() {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("test_data");
+
+    let compiler = TestCompiler::try_new(&path, true).unwrap();
+    let compiled = compiler.build().unwrap();
+    let (_, config) = &compiled.named_tests[0];
+    let span = config.definition_span.as_ref().unwrap();
+    // The span points at the name of `test_flow`, rather than at its `#[test]` attribute.
+    assert!(span.file.ends_with("lib.cairo"));
+    assert_eq!((span.line, span.col), (47, 7));
+}
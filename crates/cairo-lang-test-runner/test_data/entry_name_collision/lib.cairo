@@ -0,0 +1,10 @@
+#[inline(never)]
+fn helper() -> felt252 {
+    1
+}
+
+#[test]
+#[test_name("entry_name_collision::helper")]
+fn test_helper() {
+    assert(helper() == 1, 'wrong value');
+}
@@ -0,0 +1,17 @@
+#[test]
+#[available_gas(0)]
+#[should_panic(expected: out_of_gas)]
+fn test_out_of_gas() {
+    let mut i = 0_u32;
+    loop {
+        if i == 10 {
+            break;
+        }
+        i += 1;
+    };
+}
+
+#[test]
+fn test_with_default_gas() {
+    assert(1 + 1 == 2, 'wrong sum');
+}